@@ -0,0 +1,3 @@
+pub mod notifier;
+pub mod rules;
+pub mod sinks;
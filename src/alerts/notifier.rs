@@ -0,0 +1,266 @@
+use super::{
+    rules::{Firing, FiringKind, Rule, RuleEngine},
+    sinks::SinkConfig,
+};
+use crate::{
+    errors::Errors,
+    suilend::{
+        events::LiquidateEvent, health::ObligationHealth, postmortem::PostMortem,
+        rewards::RewardsReport, sync::PositionEvent,
+    },
+};
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fmt, path::Path, sync::Arc, time::Instant};
+use sui_sdk::types::base_types::{ObjectID, SuiAddress};
+use tracing::warn;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Warning,
+    Critical,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Info => write!(f, "INFO"),
+            Severity::Warning => write!(f, "WARNING"),
+            Severity::Critical => write!(f, "CRITICAL"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Alert {
+    pub obligation_id: ObjectID,
+    pub severity: Severity,
+    pub title: String,
+    pub message: String,
+    pub health_ratio: Option<f64>,
+    pub liquidation_drop_pct: Option<f64>,
+}
+
+impl Alert {
+    pub fn from_health(health: &ObligationHealth, severity: Severity, title: String) -> Self {
+        Self {
+            obligation_id: health.obligation_id,
+            severity,
+            title,
+            message: health.to_string(),
+            health_ratio: health.health_ratio,
+            liquidation_drop_pct: health.liquidation_drop_pct,
+        }
+    }
+
+    pub fn from_liquidation(label: &str, event: &LiquidateEvent, liquidator: SuiAddress) -> Self {
+        Self {
+            obligation_id: event.obligation_id,
+            severity: Severity::Critical,
+            title: format!("{} was liquidated", label),
+            message: format!(
+                "{} repaid {} {} and seized {} {} cTokens from obligation {}",
+                liquidator,
+                event.repay_amount,
+                event.repay_coin_type.symbol(),
+                event.withdraw_amount,
+                event.withdraw_coin_type.symbol(),
+                event.obligation_id
+            ),
+            health_ratio: None,
+            liquidation_drop_pct: None,
+        }
+    }
+
+    /// The Markdown report goes in the message so every sink gets the full post-mortem
+    pub fn from_post_mortem(report: &PostMortem) -> Self {
+        Self {
+            obligation_id: report.obligation_id,
+            severity: Severity::Critical,
+            title: format!("{} was liquidated", report.label),
+            message: report.to_markdown(),
+            health_ratio: report.health.last().map(|point| point.health_ratio),
+            liquidation_drop_pct: None,
+        }
+    }
+
+    pub fn from_position_change(event: &PositionEvent) -> Self {
+        Self {
+            obligation_id: event.obligation_id,
+            severity: Severity::Info,
+            title: format!("{} position changed", event.label),
+            message: event.to_string(),
+            health_ratio: None,
+            liquidation_drop_pct: None,
+        }
+    }
+
+    pub fn from_rewards(report: &RewardsReport) -> Self {
+        Self {
+            obligation_id: report.obligation_id,
+            severity: Severity::Info,
+            title: format!(
+                "{} has ${:.2} of unclaimed rewards",
+                report.label,
+                report.unclaimed_usd()
+            ),
+            message: report.to_string(),
+            health_ratio: None,
+            liquidation_drop_pct: None,
+        }
+    }
+}
+
+impl fmt::Display for Alert {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{}] {}\n{}", self.severity, self.title, self.message)
+    }
+}
+
+/// A destination alerts can be delivered to
+pub trait Notifier: Send + Sync {
+    fn notify<'a>(&'a self, alert: &'a Alert) -> BoxFuture<'a, Result<(), Errors>>;
+}
+
+/// Alerting config: named sinks, which sinks each obligation routes to, and health rules
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AlertsConfig {
+    #[serde(default)]
+    pub sinks: HashMap<String, SinkConfig>,
+    #[serde(default)]
+    pub default_sinks: Vec<String>,
+    #[serde(default)]
+    pub obligations: HashMap<ObjectID, Vec<String>>,
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+}
+
+impl AlertsConfig {
+    /// Reads a TOML, YAML or JSON file, picked by extension
+    pub fn from_file(path: &Path) -> Result<Self, Errors> {
+        crate::config::load_file(path)
+    }
+
+    /// No sinks and no rules, i.e. alerting is off
+    pub fn is_empty(&self) -> bool {
+        self.sinks.is_empty() && self.rules.is_empty()
+    }
+}
+
+pub struct AlertRouter {
+    sinks: HashMap<String, Arc<dyn Notifier>>,
+    default_sinks: Vec<String>,
+    routes: HashMap<ObjectID, Vec<String>>,
+    rules: RuleEngine,
+}
+
+impl AlertRouter {
+    pub fn new(config: AlertsConfig) -> Result<Self, Errors> {
+        let sinks = config
+            .sinks
+            .into_iter()
+            .map(|(name, sink)| Ok((name, sink.build()?)))
+            .collect::<Result<HashMap<_, _>, Errors>>()?;
+
+        let rule_sinks = config.rules.iter().flat_map(|rule| {
+            rule.sinks
+                .iter()
+                .chain(rule.escalation.iter().flat_map(|e| e.sinks.iter()))
+        });
+        for name in config
+            .default_sinks
+            .iter()
+            .chain(config.obligations.values().flatten())
+            .chain(rule_sinks)
+        {
+            if !sinks.contains_key(name) {
+                return Err(Errors::UnknownSink(name.clone()));
+            }
+        }
+
+        Ok(Self {
+            sinks,
+            default_sinks: config.default_sinks,
+            routes: config.obligations,
+            rules: RuleEngine::new(config.rules),
+        })
+    }
+
+    /// Switches to `config`, keeping rule state for rules that did not change.
+    ///
+    /// The router is left untouched when `config` fails to build.
+    pub fn reconfigure(&mut self, config: AlertsConfig) -> Result<(), Errors> {
+        let router = Self::new(config)?;
+        router.rules.carry_over(std::mem::take(&mut self.rules));
+        *self = router;
+        Ok(())
+    }
+
+    pub fn forget(&self, obligation_id: &ObjectID) {
+        self.rules.forget(obligation_id);
+    }
+
+    fn route(&self, obligation_id: &ObjectID) -> &[String] {
+        self.routes
+            .get(obligation_id)
+            .unwrap_or(&self.default_sinks)
+    }
+
+    /// Runs the health update through the rule engine and delivers whatever fires
+    pub fn on_health(&self, health: &ObligationHealth) {
+        let Some(current) = health.health_ratio else {
+            return;
+        };
+
+        for firing in self
+            .rules
+            .evaluate(health.obligation_id, current, Instant::now())
+        {
+            let Firing {
+                rule,
+                kind,
+                severity,
+                below,
+                sinks,
+            } = firing;
+            let title = match kind {
+                FiringKind::Triggered => format!("{}: health {:.4} below {}", rule, current, below),
+                FiringKind::Escalated => {
+                    format!("{}: health still below {} ({:.4})", rule, below, current)
+                }
+                FiringKind::Recovered => format!("{}: health recovered to {:.4}", rule, current),
+            };
+            let alert = Alert::from_health(health, severity, title);
+
+            if sinks.is_empty() {
+                self.dispatch(alert);
+            } else {
+                self.dispatch_to(alert, &sinks);
+            }
+        }
+    }
+
+    /// Sends `alert` to every sink routed for its obligation without blocking the caller
+    pub fn dispatch(&self, alert: Alert) {
+        let route = self.route(&alert.obligation_id).to_vec();
+        self.dispatch_to(alert, &route);
+    }
+
+    pub fn dispatch_to(&self, alert: Alert, sinks: &[String]) {
+        let alert = Arc::new(alert);
+        for sink in sinks
+            .iter()
+            .filter_map(|name| self.sinks.get(name).cloned())
+        {
+            let alert = alert.clone();
+            tokio::spawn(async move {
+                if let Err(e) = sink.notify(&alert).await {
+                    warn!("Failed to deliver alert for {}: {}", alert.obligation_id, e);
+                }
+            });
+        }
+    }
+}
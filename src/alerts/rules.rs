@@ -0,0 +1,325 @@
+use super::notifier::Severity;
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use sui_sdk::types::base_types::ObjectID;
+
+/// Fires while an obligation's health ratio sits below `below`.
+///
+/// Once fired the rule stays latched until health recovers above `below + hysteresis`,
+/// and never fires more often than once per `cooldown_secs`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    pub name: String,
+    pub below: f64,
+    pub severity: Severity,
+    #[serde(default)]
+    pub hysteresis: f64,
+    #[serde(default)]
+    pub cooldown_secs: u64,
+    /// Sinks to notify, defaults to the obligation's route when empty
+    #[serde(default)]
+    pub sinks: Vec<String>,
+    #[serde(default)]
+    pub escalation: Option<Escalation>,
+}
+
+impl Rule {
+    fn rearm_above(&self) -> f64 {
+        self.below + self.hysteresis.max(0.0)
+    }
+}
+
+/// Louder sinks to notify when the rule stays triggered for `after_secs`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Escalation {
+    pub after_secs: u64,
+    pub sinks: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FiringKind {
+    Triggered,
+    Escalated,
+    Recovered,
+}
+
+#[derive(Debug, Clone)]
+pub struct Firing {
+    pub rule: String,
+    pub kind: FiringKind,
+    pub severity: Severity,
+    pub below: f64,
+    /// Explicit sinks for this firing, empty means the obligation's route
+    pub sinks: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+struct RuleState {
+    /// When the rule last went from armed to triggered
+    triggered_at: Option<Instant>,
+    last_fired: Option<Instant>,
+    /// Whether the current trigger has been announced to the sinks
+    notified: bool,
+    escalated: bool,
+}
+
+/// Evaluates rules against health updates, keeping state per `(obligation_id, rule)`
+#[derive(Debug, Default)]
+pub struct RuleEngine {
+    rules: Vec<Rule>,
+    state: DashMap<(ObjectID, String), RuleState>,
+}
+
+impl RuleEngine {
+    pub fn new(rules: Vec<Rule>) -> Self {
+        Self {
+            rules,
+            state: DashMap::new(),
+        }
+    }
+
+    pub fn evaluate(&self, obligation_id: ObjectID, health: f64, now: Instant) -> Vec<Firing> {
+        let mut firings = Vec::new();
+
+        for rule in self.rules.iter() {
+            let mut state = self
+                .state
+                .entry((obligation_id, rule.name.clone()))
+                .or_default();
+            let firing = |kind, severity, sinks: &[String]| Firing {
+                rule: rule.name.clone(),
+                kind,
+                severity,
+                below: rule.below,
+                sinks: sinks.to_vec(),
+            };
+            let cooled_down = state.last_fired.is_none_or(|last| {
+                now.duration_since(last) >= Duration::from_secs(rule.cooldown_secs)
+            });
+
+            match state.triggered_at {
+                None if health < rule.below => {
+                    state.triggered_at = Some(now);
+                    state.escalated = false;
+                    state.notified = cooled_down;
+                    if cooled_down {
+                        state.last_fired = Some(now);
+                        firings.push(firing(FiringKind::Triggered, rule.severity, &rule.sinks));
+                    }
+                }
+                None => {}
+                Some(_) if health >= rule.rearm_above() => {
+                    if state.notified {
+                        firings.push(firing(FiringKind::Recovered, Severity::Info, &rule.sinks));
+                    }
+                    *state = RuleState {
+                        last_fired: state.last_fired,
+                        ..Default::default()
+                    };
+                }
+                Some(triggered_at) => {
+                    // Triggered inside a cooldown window: announce once it has elapsed
+                    if !state.notified && cooled_down && health < rule.below {
+                        state.notified = true;
+                        state.last_fired = Some(now);
+                        firings.push(firing(FiringKind::Triggered, rule.severity, &rule.sinks));
+                    }
+
+                    // Inside the hysteresis band health is recovering, not still below
+                    if let Some(escalation) =
+                        rule.escalation.as_ref().filter(|_| health < rule.below)
+                    {
+                        let persisted = now.duration_since(triggered_at)
+                            >= Duration::from_secs(escalation.after_secs);
+                        if !state.escalated && persisted {
+                            state.escalated = true;
+                            state.last_fired = Some(now);
+                            firings.push(firing(
+                                FiringKind::Escalated,
+                                Severity::Critical,
+                                &escalation.sinks,
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+
+        firings
+    }
+
+    /// Keeps the trigger and cooldown state of rules that `previous` defined identically
+    pub fn carry_over(&self, previous: RuleEngine) {
+        let unchanged = self
+            .rules
+            .iter()
+            .filter(|rule| previous.rules.contains(rule))
+            .map(|rule| rule.name.as_str())
+            .collect::<Vec<_>>();
+        for (key, state) in previous.state {
+            if unchanged.contains(&key.1.as_str()) {
+                self.state.insert(key, state);
+            }
+        }
+    }
+
+    /// Drops all state held for an obligation that is no longer watched
+    pub fn forget(&self, obligation_id: &ObjectID) {
+        self.state.retain(|(id, _), _| id != obligation_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule() -> Rule {
+        Rule {
+            name: "warn".to_string(),
+            below: 1.2,
+            severity: Severity::Warning,
+            hysteresis: 0.1,
+            cooldown_secs: 600,
+            sinks: Vec::new(),
+            escalation: Some(Escalation {
+                after_secs: 300,
+                sinks: vec!["pager".to_string()],
+            }),
+        }
+    }
+
+    fn kinds(firings: Vec<Firing>) -> Vec<FiringKind> {
+        firings.into_iter().map(|firing| firing.kind).collect()
+    }
+
+    #[test]
+    fn latches_through_the_hysteresis_band() {
+        let engine = RuleEngine::new(vec![rule()]);
+        let id = ObjectID::ZERO;
+        let start = Instant::now();
+
+        assert!(engine.evaluate(id, 1.5, start).is_empty());
+        assert_eq!(
+            kinds(engine.evaluate(id, 1.1, start)),
+            vec![FiringKind::Triggered]
+        );
+        // Above `below` but under `below + hysteresis`: still latched, nothing to say
+        assert!(engine.evaluate(id, 1.25, start).is_empty());
+        assert!(engine.evaluate(id, 1.1, start).is_empty());
+        assert_eq!(
+            kinds(engine.evaluate(id, 1.31, start)),
+            vec![FiringKind::Recovered]
+        );
+        assert!(engine.evaluate(id, 1.25, start).is_empty());
+    }
+
+    #[test]
+    fn cooldown_suppresses_retriggers() {
+        let engine = RuleEngine::new(vec![Rule {
+            escalation: None,
+            ..rule()
+        }]);
+        let id = ObjectID::ZERO;
+        let start = Instant::now();
+
+        assert_eq!(
+            kinds(engine.evaluate(id, 1.0, start)),
+            vec![FiringKind::Triggered]
+        );
+        assert_eq!(
+            kinds(engine.evaluate(id, 1.5, start + Duration::from_secs(10))),
+            vec![FiringKind::Recovered]
+        );
+        // Drops again within the cooldown: held back, then announced once it elapses
+        assert!(engine
+            .evaluate(id, 1.0, start + Duration::from_secs(20))
+            .is_empty());
+        assert!(engine
+            .evaluate(id, 1.0, start + Duration::from_secs(599))
+            .is_empty());
+        assert_eq!(
+            kinds(engine.evaluate(id, 1.0, start + Duration::from_secs(600))),
+            vec![FiringKind::Triggered]
+        );
+        assert!(engine
+            .evaluate(id, 1.0, start + Duration::from_secs(1300))
+            .is_empty());
+    }
+
+    #[test]
+    fn recovery_is_silent_for_unannounced_triggers() {
+        let engine = RuleEngine::new(vec![Rule {
+            escalation: None,
+            ..rule()
+        }]);
+        let id = ObjectID::ZERO;
+        let start = Instant::now();
+
+        engine.evaluate(id, 1.0, start);
+        engine.evaluate(id, 1.5, start + Duration::from_secs(10));
+        assert!(engine
+            .evaluate(id, 1.0, start + Duration::from_secs(20))
+            .is_empty());
+        assert!(engine
+            .evaluate(id, 1.5, start + Duration::from_secs(30))
+            .is_empty());
+    }
+
+    #[test]
+    fn escalates_once_after_the_delay() {
+        let engine = RuleEngine::new(vec![rule()]);
+        let id = ObjectID::ZERO;
+        let start = Instant::now();
+
+        engine.evaluate(id, 1.0, start);
+        assert!(engine
+            .evaluate(id, 1.0, start + Duration::from_secs(299))
+            .is_empty());
+        let firings = engine.evaluate(id, 1.0, start + Duration::from_secs(300));
+        assert_eq!(firings.len(), 1);
+        assert_eq!(firings[0].kind, FiringKind::Escalated);
+        assert_eq!(firings[0].severity, Severity::Critical);
+        assert_eq!(firings[0].sinks, vec!["pager".to_string()]);
+        assert!(engine
+            .evaluate(id, 1.0, start + Duration::from_secs(900))
+            .is_empty());
+    }
+
+    #[test]
+    fn does_not_escalate_inside_the_hysteresis_band() {
+        let engine = RuleEngine::new(vec![rule()]);
+        let id = ObjectID::ZERO;
+        let start = Instant::now();
+
+        engine.evaluate(id, 1.0, start);
+        assert!(engine
+            .evaluate(id, 1.25, start + Duration::from_secs(400))
+            .is_empty());
+        // Dropping back below while still latched escalates then
+        assert_eq!(
+            kinds(engine.evaluate(id, 1.1, start + Duration::from_secs(401))),
+            vec![FiringKind::Escalated]
+        );
+    }
+
+    #[test]
+    fn state_is_kept_per_obligation() {
+        let engine = RuleEngine::new(vec![rule()]);
+        let start = Instant::now();
+        let other = ObjectID::new([1; 32]);
+
+        engine.evaluate(ObjectID::ZERO, 1.0, start);
+        assert_eq!(
+            kinds(engine.evaluate(other, 1.0, start)),
+            vec![FiringKind::Triggered]
+        );
+        engine.forget(&other);
+        assert_eq!(
+            kinds(engine.evaluate(other, 1.0, start)),
+            vec![FiringKind::Triggered]
+        );
+    }
+}
@@ -0,0 +1,544 @@
+use super::notifier::{Alert, Notifier, Severity};
+use crate::errors::Errors;
+use futures::future::BoxFuture;
+use lettre::{
+    message::Mailbox, transport::smtp::authentication::Credentials, AsyncSmtpTransport,
+    AsyncTransport, Message, Tokio1Executor,
+};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::sync::Arc;
+
+/// Sink definitions as they appear in the alerts config
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SinkConfig {
+    Stdout,
+    Desktop,
+    Telegram {
+        bot_token: String,
+        chat_id: String,
+        /// Override for the Bot API base url, e.g. a local mock server
+        #[serde(default = "TelegramNotifier::default_api_base")]
+        api_base: String,
+    },
+    Discord {
+        webhook_url: String,
+    },
+    Slack {
+        webhook_url: String,
+    },
+    Webhook {
+        url: String,
+        #[serde(default)]
+        headers: Vec<(String, String)>,
+    },
+    Email {
+        host: String,
+        #[serde(default = "EmailNotifier::default_port")]
+        port: u16,
+        #[serde(default)]
+        username: Option<String>,
+        #[serde(default)]
+        password: Option<String>,
+        /// Use STARTTLS; disable for plaintext local servers
+        #[serde(default = "EmailNotifier::default_tls")]
+        tls: bool,
+        from: String,
+        to: Vec<String>,
+    },
+}
+
+impl SinkConfig {
+    pub fn build(self) -> Result<Arc<dyn Notifier>, Errors> {
+        let client = Client::new();
+        Ok(match self {
+            SinkConfig::Stdout => Arc::new(StdoutNotifier),
+            SinkConfig::Desktop => Arc::new(DesktopNotifier),
+            SinkConfig::Telegram {
+                bot_token,
+                chat_id,
+                api_base,
+            } => Arc::new(TelegramNotifier {
+                client,
+                api_base,
+                bot_token,
+                chat_id,
+            }),
+            SinkConfig::Discord { webhook_url } => Arc::new(DiscordNotifier {
+                client,
+                webhook_url,
+            }),
+            SinkConfig::Slack { webhook_url } => Arc::new(SlackNotifier {
+                client,
+                webhook_url,
+            }),
+            SinkConfig::Webhook { url, headers } => Arc::new(WebhookNotifier {
+                client,
+                url,
+                headers,
+            }),
+            SinkConfig::Email {
+                host,
+                port,
+                username,
+                password,
+                tls,
+                from,
+                to,
+            } => {
+                let credentials = match (username, password) {
+                    (Some(username), Some(password)) => Some((username, password)),
+                    (None, None) => None,
+                    _ => return Err(Errors::IncompleteSmtpCredentials),
+                };
+                Arc::new(EmailNotifier::new(
+                    &host,
+                    port,
+                    credentials,
+                    tls,
+                    &from,
+                    &to,
+                )?)
+            }
+        })
+    }
+}
+
+pub struct StdoutNotifier;
+
+impl Notifier for StdoutNotifier {
+    fn notify<'a>(&'a self, alert: &'a Alert) -> BoxFuture<'a, Result<(), Errors>> {
+        Box::pin(async move {
+            println!("\n{}\n", alert);
+            Ok(())
+        })
+    }
+}
+
+/// Desktop notification through `notify-send`
+pub struct DesktopNotifier;
+
+impl Notifier for DesktopNotifier {
+    fn notify<'a>(&'a self, alert: &'a Alert) -> BoxFuture<'a, Result<(), Errors>> {
+        Box::pin(async move {
+            let urgency = match alert.severity {
+                Severity::Critical => "critical",
+                _ => "normal",
+            };
+            tokio::process::Command::new("notify-send")
+                .args(["-u", urgency, &alert.title, &alert.message])
+                .status()
+                .await?;
+            Ok(())
+        })
+    }
+}
+
+pub struct TelegramNotifier {
+    client: Client,
+    api_base: String,
+    bot_token: String,
+    chat_id: String,
+}
+
+impl TelegramNotifier {
+    fn default_api_base() -> String {
+        "https://api.telegram.org".to_string()
+    }
+}
+
+impl Notifier for TelegramNotifier {
+    fn notify<'a>(&'a self, alert: &'a Alert) -> BoxFuture<'a, Result<(), Errors>> {
+        Box::pin(async move {
+            self.client
+                .post(format!(
+                    "{}/bot{}/sendMessage",
+                    self.api_base, self.bot_token
+                ))
+                .json(&json!({ "chat_id": self.chat_id, "text": alert.to_string() }))
+                .send()
+                .await?
+                .error_for_status()?;
+            Ok(())
+        })
+    }
+}
+
+pub struct DiscordNotifier {
+    client: Client,
+    webhook_url: String,
+}
+
+impl Notifier for DiscordNotifier {
+    fn notify<'a>(&'a self, alert: &'a Alert) -> BoxFuture<'a, Result<(), Errors>> {
+        Box::pin(async move {
+            self.client
+                .post(&self.webhook_url)
+                .json(&json!({ "content": alert.to_string() }))
+                .send()
+                .await?
+                .error_for_status()?;
+            Ok(())
+        })
+    }
+}
+
+pub struct SlackNotifier {
+    client: Client,
+    webhook_url: String,
+}
+
+impl Notifier for SlackNotifier {
+    fn notify<'a>(&'a self, alert: &'a Alert) -> BoxFuture<'a, Result<(), Errors>> {
+        Box::pin(async move {
+            self.client
+                .post(&self.webhook_url)
+                .json(&json!({ "text": alert.to_string() }))
+                .send()
+                .await?
+                .error_for_status()?;
+            Ok(())
+        })
+    }
+}
+
+/// Posts the alert as a JSON body
+pub struct WebhookNotifier {
+    client: Client,
+    url: String,
+    headers: Vec<(String, String)>,
+}
+
+impl Notifier for WebhookNotifier {
+    fn notify<'a>(&'a self, alert: &'a Alert) -> BoxFuture<'a, Result<(), Errors>> {
+        Box::pin(async move {
+            let request = self
+                .headers
+                .iter()
+                .fold(self.client.post(&self.url), |request, (key, value)| {
+                    request.header(key, value)
+                });
+            request.json(alert).send().await?.error_for_status()?;
+            Ok(())
+        })
+    }
+}
+
+pub struct EmailNotifier {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+    to: Vec<Mailbox>,
+}
+
+impl EmailNotifier {
+    fn default_port() -> u16 {
+        587
+    }
+
+    fn default_tls() -> bool {
+        true
+    }
+
+    pub fn new(
+        host: &str,
+        port: u16,
+        credentials: Option<(String, String)>,
+        tls: bool,
+        from: &str,
+        to: &[String],
+    ) -> Result<Self, Errors> {
+        let builder = if tls {
+            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?
+        } else {
+            AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host)
+        };
+        let builder = match credentials {
+            Some((username, password)) => builder.credentials(Credentials::new(username, password)),
+            None => builder,
+        };
+
+        Ok(Self {
+            transport: builder.port(port).build(),
+            from: from.parse()?,
+            to: to.iter().map(|to| to.parse()).collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+impl Notifier for EmailNotifier {
+    fn notify<'a>(&'a self, alert: &'a Alert) -> BoxFuture<'a, Result<(), Errors>> {
+        Box::pin(async move {
+            let message = self
+                .to
+                .iter()
+                .cloned()
+                .fold(Message::builder().from(self.from.clone()), |message, to| {
+                    message.to(to)
+                })
+                .subject(format!("[{}] {}", alert.severity, alert.title))
+                .body(alert.message.clone())?;
+            self.transport.send(message).await?;
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sui_sdk::types::base_types::ObjectID;
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+        net::TcpListener,
+        task::JoinHandle,
+    };
+
+    struct Request {
+        request_line: String,
+        headers: Vec<(String, String)>,
+        body: Vec<u8>,
+    }
+
+    impl Request {
+        fn header(&self, name: &str) -> Option<&str> {
+            self.headers
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.as_str())
+        }
+
+        fn json(&self) -> serde_json::Value {
+            serde_json::from_slice(&self.body).unwrap()
+        }
+    }
+
+    fn alert() -> Alert {
+        Alert {
+            obligation_id: ObjectID::ZERO,
+            severity: Severity::Critical,
+            title: "main is close to liquidation".to_string(),
+            message: "Health: 1.0100".to_string(),
+            health_ratio: Some(1.01),
+            liquidation_drop_pct: Some(0.99),
+        }
+    }
+
+    /// Answers a single HTTP request with `200 OK` and hands it back
+    async fn mock_http() -> (String, JoinHandle<Request>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let handle = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = BufReader::new(stream);
+            let mut request_line = String::new();
+            stream.read_line(&mut request_line).await.unwrap();
+
+            let mut headers = Vec::new();
+            loop {
+                let mut line = String::new();
+                stream.read_line(&mut line).await.unwrap();
+                let line = line.trim_end();
+                if line.is_empty() {
+                    break;
+                }
+                let (key, value) = line.split_once(':').unwrap();
+                headers.push((key.trim().to_string(), value.trim().to_string()));
+            }
+            let mut request = Request {
+                request_line: request_line.trim_end().to_string(),
+                headers,
+                body: Vec::new(),
+            };
+            let length = request
+                .header("content-length")
+                .map_or(0, |length| length.parse().unwrap());
+            request.body.resize(length, 0);
+            stream.read_exact(&mut request.body).await.unwrap();
+
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\nconnection: close\r\n\r\n")
+                .await
+                .unwrap();
+            request
+        });
+        (base, handle)
+    }
+
+    async fn deliver(sink: SinkConfig) {
+        sink.build().unwrap().notify(&alert()).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn telegram_posts_to_the_bot_api() {
+        let (base, request) = mock_http().await;
+        deliver(SinkConfig::Telegram {
+            bot_token: "123:abc".to_string(),
+            chat_id: "42".to_string(),
+            api_base: base,
+        })
+        .await;
+
+        let request = request.await.unwrap();
+        assert_eq!(
+            request.request_line,
+            "POST /bot123:abc/sendMessage HTTP/1.1"
+        );
+        let body = request.json();
+        assert_eq!(body["chat_id"], "42");
+        assert_eq!(body["text"], alert().to_string());
+    }
+
+    #[tokio::test]
+    async fn discord_posts_content() {
+        let (base, request) = mock_http().await;
+        deliver(SinkConfig::Discord {
+            webhook_url: format!("{}/api/webhooks/1/token", base),
+        })
+        .await;
+
+        let request = request.await.unwrap();
+        assert_eq!(request.request_line, "POST /api/webhooks/1/token HTTP/1.1");
+        assert_eq!(request.json()["content"], alert().to_string());
+    }
+
+    #[tokio::test]
+    async fn slack_posts_text() {
+        let (base, request) = mock_http().await;
+        deliver(SinkConfig::Slack {
+            webhook_url: format!("{}/services/T/B/X", base),
+        })
+        .await;
+
+        let request = request.await.unwrap();
+        assert_eq!(request.request_line, "POST /services/T/B/X HTTP/1.1");
+        assert_eq!(request.json()["text"], alert().to_string());
+    }
+
+    #[tokio::test]
+    async fn webhook_posts_the_alert_with_headers() {
+        let (base, request) = mock_http().await;
+        deliver(SinkConfig::Webhook {
+            url: format!("{}/hook", base),
+            headers: vec![("Authorization".to_string(), "Bearer secret".to_string())],
+        })
+        .await;
+
+        let request = request.await.unwrap();
+        assert_eq!(request.request_line, "POST /hook HTTP/1.1");
+        assert_eq!(request.header("authorization"), Some("Bearer secret"));
+        let body = request.json();
+        assert_eq!(body["severity"], "critical");
+        assert_eq!(body["title"], alert().title);
+        assert_eq!(body["health_ratio"], 1.01);
+    }
+
+    #[tokio::test]
+    async fn webhook_reports_error_statuses() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buffer = [0u8; 4096];
+            let _ = stream.read(&mut buffer).await.unwrap();
+            stream
+                .write_all(b"HTTP/1.1 500 Internal Server Error\r\ncontent-length: 0\r\n\r\n")
+                .await
+                .unwrap();
+        });
+
+        let sink = SinkConfig::Webhook {
+            url,
+            headers: Vec::new(),
+        };
+        let result = sink.build().unwrap().notify(&alert()).await;
+        assert!(matches!(result, Err(Errors::HttpError(_))));
+    }
+
+    /// Speaks just enough SMTP to accept one message, returning the commands and the data
+    async fn mock_smtp() -> (u16, JoinHandle<(Vec<String>, String)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (read, mut write) = stream.into_split();
+            let mut read = BufReader::new(read);
+            write.write_all(b"220 localhost ESMTP\r\n").await.unwrap();
+
+            let (mut commands, mut data) = (Vec::new(), String::new());
+            loop {
+                let mut line = String::new();
+                if read.read_line(&mut line).await.unwrap() == 0 {
+                    break;
+                }
+                let command = line.trim_end().to_string();
+                let reply: &[u8] = match command.to_ascii_uppercase() {
+                    c if c.starts_with("EHLO") => b"250 localhost\r\n",
+                    c if c.starts_with("DATA") => {
+                        write.write_all(b"354 go ahead\r\n").await.unwrap();
+                        loop {
+                            let mut line = String::new();
+                            read.read_line(&mut line).await.unwrap();
+                            if line == ".\r\n" {
+                                break;
+                            }
+                            data.push_str(&line);
+                        }
+                        b"250 queued\r\n"
+                    }
+                    c if c.starts_with("QUIT") => {
+                        write.write_all(b"221 bye\r\n").await.unwrap();
+                        commands.push(command);
+                        break;
+                    }
+                    _ => b"250 ok\r\n",
+                };
+                commands.push(command);
+                write.write_all(reply).await.unwrap();
+            }
+            (commands, data)
+        });
+        (port, handle)
+    }
+
+    #[tokio::test]
+    async fn email_sends_over_plain_smtp() {
+        let (port, session) = mock_smtp().await;
+        deliver(SinkConfig::Email {
+            host: "127.0.0.1".to_string(),
+            port,
+            username: None,
+            password: None,
+            tls: false,
+            from: "monitor@example.com".to_string(),
+            to: vec!["ops@example.com".to_string()],
+        })
+        .await;
+
+        let (commands, data) = session.await.unwrap();
+        assert!(commands
+            .iter()
+            .any(|c| c == "MAIL FROM:<monitor@example.com>"));
+        assert!(commands.iter().any(|c| c == "RCPT TO:<ops@example.com>"));
+        assert!(data.contains("Subject: [CRITICAL] main is close to liquidation"));
+        assert!(data.contains("Health: 1.0100"));
+    }
+
+    #[test]
+    fn email_rejects_half_credentials() {
+        let sink = SinkConfig::Email {
+            host: "127.0.0.1".to_string(),
+            port: 25,
+            username: Some("monitor".to_string()),
+            password: None,
+            tls: false,
+            from: "monitor@example.com".to_string(),
+            to: vec!["ops@example.com".to_string()],
+        };
+        assert!(matches!(
+            sink.build(),
+            Err(Errors::IncompleteSmtpCredentials)
+        ));
+    }
+}
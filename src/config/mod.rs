@@ -0,0 +1,187 @@
+pub mod reload;
+pub mod validation;
+
+use crate::{
+    alerts::notifier::AlertsConfig,
+    errors::Errors,
+    pyth::{registry::FeedMetadata, stream::StreamConfig},
+    rpc::RpcConfig,
+    suilend::{
+        events::EventsConfig, postmortem::PostMortemConfig, reserves::MarketConfig,
+        rewards::RewardsConfig, sync::SyncConfig,
+    },
+    watch::WatchTarget,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{fmt, path::Path, time::Duration};
+use validation::{ConfigIssue, ConfigIssues};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Toml,
+    Yaml,
+    Json,
+}
+
+impl Format {
+    /// Picks the format from the file extension, JSON when there is none
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => Format::Toml,
+            Some("yaml") | Some("yml") => Format::Yaml,
+            _ => Format::Json,
+        }
+    }
+
+    pub fn parse<T: DeserializeOwned>(&self, source: &str) -> Result<T, ConfigIssue> {
+        match self {
+            Format::Toml => toml::from_str(source).map_err(|e| {
+                let line = e.span().map(|span| validation::line_at(source, span.start));
+                ConfigIssue::new(line, e.message())
+            }),
+            Format::Yaml => serde_yaml::from_str(source).map_err(|e| {
+                let line = e.location().map(|location| location.line());
+                ConfigIssue::new(line, e)
+            }),
+            Format::Json => {
+                serde_json::from_str(source).map_err(|e| ConfigIssue::new(Some(e.line()), e))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Format::Toml => write!(f, "TOML"),
+            Format::Yaml => write!(f, "YAML"),
+            Format::Json => write!(f, "JSON"),
+        }
+    }
+}
+
+/// Reads any config section from a TOML, YAML or JSON file
+pub fn load_file<T: DeserializeOwned>(path: &Path) -> Result<T, Errors> {
+    let source = std::fs::read_to_string(path)?;
+    Format::from_path(path)
+        .parse(&source)
+        .map_err(|issue| Errors::InvalidConfig {
+            path: path.display().to_string(),
+            issues: ConfigIssues(vec![issue]),
+        })
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PythConfig {
+    pub hermes_url: String,
+    pub initial_backoff_ms: u64,
+    pub max_backoff_secs: u64,
+    /// Reconnect when Hermes sends nothing for this long
+    pub heartbeat_timeout_secs: u64,
+    /// Updates buffered per subscriber before it starts skipping
+    pub broadcast_capacity: usize,
+    /// Refresh the feed registry from Hermes at startup instead of only using the bundled list
+    pub fetch_feeds: bool,
+    /// Feeds to add or correct in the registry
+    pub feeds: Vec<FeedMetadata>,
+    /// Also stream prices of the reward tokens our positions earn
+    pub reward_feeds: bool,
+}
+
+impl Default for PythConfig {
+    fn default() -> Self {
+        let stream = StreamConfig::default();
+        Self {
+            hermes_url: stream.hermes_url,
+            initial_backoff_ms: stream.initial_backoff.as_millis() as u64,
+            max_backoff_secs: stream.max_backoff.as_secs(),
+            heartbeat_timeout_secs: stream.heartbeat_timeout.as_secs(),
+            broadcast_capacity: 1024,
+            fetch_feeds: true,
+            feeds: Vec::new(),
+            reward_feeds: false,
+        }
+    }
+}
+
+impl PythConfig {
+    pub fn stream_config(&self) -> StreamConfig {
+        StreamConfig {
+            hermes_url: self.hermes_url.trim_end_matches('/').to_string(),
+            initial_backoff: Duration::from_millis(self.initial_backoff_ms),
+            max_backoff: Duration::from_secs(self.max_backoff_secs),
+            heartbeat_timeout: Duration::from_secs(self.heartbeat_timeout_secs),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingConfig {
+    /// `tracing` filter directives, e.g. `info` or `position_health_monitor=debug`
+    pub level: String,
+    pub ansi: bool,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            level: "info".to_string(),
+            ansi: true,
+        }
+    }
+}
+
+/// The whole monitor: what to watch, where to read it from and who to tell
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MonitorConfig {
+    pub targets: Vec<WatchTarget>,
+    pub alerts: AlertsConfig,
+    pub rpc: RpcConfig,
+    pub pyth: PythConfig,
+    pub suilend: MarketConfig,
+    pub sync: SyncConfig,
+    pub events: EventsConfig,
+    pub postmortem: PostMortemConfig,
+    pub rewards: RewardsConfig,
+    pub logging: LoggingConfig,
+}
+
+impl MonitorConfig {
+    /// Loads and validates a config file, reporting every problem found
+    pub fn from_file(path: &Path) -> Result<Self, Errors> {
+        let source = std::fs::read_to_string(path)?;
+        Self::parse(&source, Format::from_path(path)).map_err(|issues| Errors::InvalidConfig {
+            path: path.display().to_string(),
+            issues,
+        })
+    }
+
+    /// Re-checks a config merged from several files and flags, which has no single source to
+    /// point line numbers into; `origin` names it in the error
+    pub fn validate(&self, origin: &str) -> Result<(), Errors> {
+        let issues = validation::validate(self, "");
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(Errors::InvalidConfig {
+                path: origin.to_string(),
+                issues: ConfigIssues(issues),
+            })
+        }
+    }
+
+    pub fn parse(source: &str, format: Format) -> Result<Self, ConfigIssues> {
+        let config: Self = format
+            .parse(source)
+            .map_err(|issue| ConfigIssues(vec![issue]))?;
+        let issues = validation::validate(&config, source);
+        if issues.is_empty() {
+            Ok(config)
+        } else {
+            Err(ConfigIssues(issues))
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use super::MonitorConfig;
+use crate::errors::Errors;
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+/// How often the config file's modification time is checked
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Watches `path` for edits and SIGHUP, sending every config that loads and validates.
+///
+/// `load` re-reads the file and re-applies anything layered on top of it, such as CLI flags.
+/// A config that fails is logged and dropped so the running one stays in effect.
+pub fn spawn<F>(path: PathBuf, load: F) -> mpsc::Receiver<MonitorConfig>
+where
+    F: Fn(&Path) -> Result<MonitorConfig, Errors> + Send + 'static,
+{
+    let (sender, receiver) = mpsc::channel(4);
+    tokio::spawn(async move {
+        let mut hangup = Hangup::new();
+        let mut ticker = tokio::time::interval(POLL_INTERVAL);
+        let mut last_modified = modified(&path);
+
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {
+                    let current = modified(&path);
+                    if current == last_modified {
+                        continue;
+                    }
+                    last_modified = current;
+                    info!("{} changed, reloading", path.display());
+                }
+                _ = hangup.recv() => info!("SIGHUP received, reloading {}", path.display()),
+            }
+
+            match load(&path) {
+                Ok(config) => {
+                    if sender.send(config).await.is_err() {
+                        return;
+                    }
+                }
+                Err(e) => warn!("Rejected config change, keeping the running config: {}", e),
+            }
+        }
+    });
+    receiver
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+#[cfg(unix)]
+struct Hangup(Option<tokio::signal::unix::Signal>);
+
+#[cfg(unix)]
+impl Hangup {
+    fn new() -> Self {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::hangup()) {
+            Ok(signal) => Self(Some(signal)),
+            Err(e) => {
+                warn!("Cannot listen for SIGHUP: {}", e);
+                Self(None)
+            }
+        }
+    }
+
+    async fn recv(&mut self) {
+        match self.0.as_mut() {
+            Some(signal) => {
+                signal.recv().await;
+            }
+            None => std::future::pending().await,
+        }
+    }
+}
+
+#[cfg(not(unix))]
+struct Hangup;
+
+#[cfg(not(unix))]
+impl Hangup {
+    fn new() -> Self {
+        Hangup
+    }
+
+    async fn recv(&mut self) {
+        std::future::pending().await
+    }
+}
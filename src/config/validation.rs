@@ -0,0 +1,417 @@
+use super::MonitorConfig;
+use crate::{alerts::sinks::SinkConfig, suilend::reserves::MarketConfig};
+use lettre::message::Mailbox;
+use reqwest::Url;
+use std::{collections::HashMap, fmt};
+use tracing_subscriber::EnvFilter;
+
+/// A single problem in a config file, with the 1-based line it was found on when known
+#[derive(Debug, Clone)]
+pub struct ConfigIssue {
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl ConfigIssue {
+    pub fn new(line: Option<usize>, message: impl fmt::Display) -> Self {
+        Self {
+            line,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {}: {}", line, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ConfigIssues(pub Vec<ConfigIssue>);
+
+impl fmt::Display for ConfigIssues {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for issue in self.0.iter() {
+            write!(f, "\n  {}", issue)?;
+        }
+        Ok(())
+    }
+}
+
+/// 1-based line of the byte `offset` in `source`
+pub fn line_at(source: &str, offset: usize) -> usize {
+    source[..offset.min(source.len())].matches('\n').count() + 1
+}
+
+/// Offsets of `word` from `from` on that stand on their own and are not commented out
+fn occurrences<'a>(
+    source: &'a str,
+    from: usize,
+    word: &'a str,
+) -> impl Iterator<Item = usize> + 'a {
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_' || c == '-';
+    source[from..]
+        .match_indices(word)
+        .map(move |(i, _)| from + i)
+        .filter(move |&offset| {
+            let line_start = source[..offset].rfind('\n').map_or(0, |i| i + 1);
+            !source[..offset].chars().next_back().is_some_and(is_ident)
+                && !source[offset + word.len()..]
+                    .chars()
+                    .next()
+                    .is_some_and(is_ident)
+                && !source[line_start..offset].contains('#')
+        })
+}
+
+/// Offset of the dotted key `path`, each segment looked up after the one before it so
+/// that e.g. `sync.interval_secs` lands in `[sync]` whatever the format
+fn find_key(source: &str, path: &str) -> Option<usize> {
+    path.split('.')
+        .try_fold(0, |from, key| occurrences(source, from, key).next())
+}
+
+struct Validator<'a> {
+    source: &'a str,
+    issues: Vec<ConfigIssue>,
+}
+
+impl Validator<'_> {
+    fn push(&mut self, offset: Option<usize>, message: impl fmt::Display) {
+        let line = offset.map(|offset| line_at(self.source, offset));
+        self.issues.push(ConfigIssue::new(line, message));
+    }
+
+    /// Reports a problem with the key at the dotted `path`
+    fn report(&mut self, path: &str, message: impl fmt::Display) {
+        self.push(find_key(self.source, path), message);
+    }
+
+    /// Reports a problem with the `nth` occurrence of `value` inside `section`
+    fn report_value(&mut self, section: &str, value: &str, nth: usize, message: impl fmt::Display) {
+        let offset = find_key(self.source, section)
+            .and_then(|from| occurrences(self.source, from, value).nth(nth));
+        self.push(offset, message);
+    }
+
+    fn check_url(&mut self, section: &str, field: &str, url: &str) {
+        if let Err(e) = Url::parse(url) {
+            self.report_value(
+                section,
+                url,
+                0,
+                format!("invalid URL `{}` for `{}`: {}", url, field, e),
+            );
+        }
+    }
+
+    fn check_positive(&mut self, path: &str, value: u64) {
+        if value == 0 {
+            let field = path.rsplit('.').next().unwrap_or(path);
+            self.report(path, format!("`{}` must be greater than zero", field));
+        }
+    }
+}
+
+/// Semantic checks that deserialization alone can't express
+pub fn validate(config: &MonitorConfig, source: &str) -> Vec<ConfigIssue> {
+    let mut v = Validator {
+        source,
+        issues: Vec::new(),
+    };
+
+    for endpoint in config.rpc.endpoints.iter() {
+        v.check_url("rpc.endpoints", "url", &endpoint.url);
+        if endpoint.rate_limit == Some(0) {
+            v.report_value(
+                "rpc.endpoints",
+                &endpoint.url,
+                0,
+                "`rate_limit` must be greater than zero",
+            );
+        }
+    }
+    v.check_positive("rpc.health_check_secs", config.rpc.health_check_secs);
+    v.check_positive("rpc.request_timeout_secs", config.rpc.request_timeout_secs);
+
+    v.check_url("pyth", "hermes_url", &config.pyth.hermes_url);
+    v.check_positive("pyth.initial_backoff_ms", config.pyth.initial_backoff_ms);
+    v.check_positive(
+        "pyth.heartbeat_timeout_secs",
+        config.pyth.heartbeat_timeout_secs,
+    );
+    v.check_positive(
+        "pyth.broadcast_capacity",
+        config.pyth.broadcast_capacity as u64,
+    );
+    if config.pyth.max_backoff_secs.saturating_mul(1000) < config.pyth.initial_backoff_ms {
+        v.report(
+            "pyth.max_backoff_secs",
+            "`max_backoff_secs` is shorter than `initial_backoff_ms`",
+        );
+    }
+
+    v.check_positive("sync.interval_secs", config.sync.interval_secs);
+    v.check_positive("events.poll_interval_ms", config.events.poll_interval_ms);
+    v.check_positive("events.page_size", config.events.page_size as u64);
+    v.check_positive("postmortem.history_secs", config.postmortem.history_secs);
+    v.check_positive("rewards.interval_secs", config.rewards.interval_secs);
+    if config.rewards.alert_above_usd.is_some_and(|usd| usd <= 0.0) {
+        v.report(
+            "rewards.alert_above_usd",
+            "`alert_above_usd` must be greater than zero",
+        );
+    }
+    for coin_type in config.suilend.coin_feeds.keys() {
+        if !coin_type.contains("::") {
+            v.report_value(
+                "suilend.coin_feeds",
+                coin_type,
+                0,
+                format!("`{}` is not a coin type", coin_type),
+            );
+        }
+    }
+    if let Err(e) = config.suilend.package_id() {
+        v.report("suilend.package", e);
+    }
+
+    for pool in config.suilend.pools.iter() {
+        if let Err(e) = MarketConfig::pool_type(pool) {
+            v.report_value("suilend.pools", pool, 0, e);
+        }
+    }
+
+    if let Err(e) = EnvFilter::try_new(&config.logging.level) {
+        v.report(
+            "logging.level",
+            format!("invalid log level `{}`: {}", config.logging.level, e),
+        );
+    }
+
+    let alerts = &config.alerts;
+    for (name, sink) in alerts.sinks.iter() {
+        let section = format!("alerts.sinks.{}", name);
+        match sink {
+            SinkConfig::Telegram { api_base, .. } => v.check_url(&section, "api_base", api_base),
+            SinkConfig::Discord { webhook_url } | SinkConfig::Slack { webhook_url } => {
+                v.check_url(&section, "webhook_url", webhook_url)
+            }
+            SinkConfig::Webhook { url, .. } => v.check_url(&section, "url", url),
+            SinkConfig::Email {
+                username,
+                password,
+                from,
+                to,
+                ..
+            } => {
+                if username.is_some() != password.is_some() {
+                    v.report(
+                        &section,
+                        format!(
+                            "email sink `{}` needs both `username` and `password`, or neither",
+                            name
+                        ),
+                    );
+                }
+                for address in std::iter::once(from).chain(to.iter()) {
+                    if address.parse::<Mailbox>().is_err() {
+                        v.report_value(
+                            &section,
+                            address,
+                            0,
+                            format!("sink `{}` has an invalid email address `{}`", name, address),
+                        );
+                    }
+                }
+                if to.is_empty() {
+                    v.report(&section, format!("email sink `{}` has no recipients", name));
+                }
+            }
+            SinkConfig::Stdout | SinkConfig::Desktop => {}
+        }
+    }
+
+    let rule_sinks = alerts.rules.iter().flat_map(|rule| {
+        rule.sinks
+            .iter()
+            .chain(rule.escalation.iter().flat_map(|e| e.sinks.iter()))
+    });
+    for name in alerts
+        .default_sinks
+        .iter()
+        .chain(alerts.obligations.values().flatten())
+        .chain(rule_sinks)
+    {
+        if !alerts.sinks.contains_key(name) {
+            v.report_value(
+                "alerts",
+                name,
+                0,
+                format!("alert sink `{}` is not defined", name),
+            );
+        }
+    }
+
+    let mut rule_names = HashMap::new();
+    for rule in alerts.rules.iter() {
+        // Which definition of this name it is, to point at the right one
+        let nth = rule_names.entry(rule.name.as_str()).or_insert(0);
+        let mut report =
+            |message: String| v.report_value("alerts.rules", &rule.name, *nth, message);
+        if *nth > 0 {
+            report(format!("rule `{}` is defined twice", rule.name));
+        }
+        if !rule.below.is_finite() || rule.below <= 0.0 {
+            report(format!(
+                "rule `{}` needs a positive `below` threshold",
+                rule.name
+            ));
+        }
+        if rule.hysteresis < 0.0 {
+            report(format!("rule `{}` has a negative `hysteresis`", rule.name));
+        }
+        *nth += 1;
+    }
+
+    v.issues
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{Format, MonitorConfig};
+
+    fn issues(source: &str, format: Format) -> Vec<(Option<usize>, String)> {
+        match MonitorConfig::parse(source, format) {
+            Ok(_) => Vec::new(),
+            Err(issues) => issues
+                .0
+                .into_iter()
+                .map(|issue| (issue.line, issue.message))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn reports_repeated_keys_in_their_own_section() {
+        let source = "\
+[events]
+poll_interval_ms = 2000
+
+[sync]
+interval_secs = 0
+
+[logging]
+level = \"not a level[\"
+";
+        let issues = issues(source, Format::Toml);
+        assert_eq!(issues.len(), 2, "{:?}", issues);
+        assert_eq!(issues[0].0, Some(5));
+        assert!(issues[0].1.contains("`interval_secs`"));
+        assert_eq!(issues[1].0, Some(8));
+    }
+
+    #[test]
+    fn tells_rewards_and_sync_intervals_apart() {
+        let source = "\
+[sync]
+interval_secs = 60
+
+[rewards]
+interval_secs = 0
+alert_above_usd = -1.0
+";
+        let issues = issues(source, Format::Toml);
+        assert_eq!(issues.len(), 2, "{:?}", issues);
+        assert_eq!(issues[0].0, Some(5));
+        assert_eq!(issues[1].0, Some(6));
+    }
+
+    #[test]
+    fn ignores_commented_out_keys() {
+        let source = "\
+# [sync]
+# interval_secs = 60
+[sync]
+interval_secs = 0
+";
+        assert_eq!(issues(source, Format::Toml)[0].0, Some(4));
+    }
+
+    #[test]
+    fn finds_nested_keys_in_yaml_and_json() {
+        let yaml = "\
+pyth:
+  initial_backoff_ms: 500
+sync:
+  interval_secs: 0
+";
+        assert_eq!(issues(yaml, Format::Yaml)[0].0, Some(4));
+
+        let json = "{\n  \"pyth\": { \"initial_backoff_ms\": 0 }\n}";
+        assert_eq!(issues(json, Format::Json)[0].0, Some(2));
+    }
+
+    #[test]
+    fn points_at_the_second_definition_of_a_rule() {
+        let source = "\
+[alerts]
+default_sinks = []
+
+[[alerts.rules]]
+name = \"warn\"
+below = 1.2
+severity = \"warning\"
+
+[[alerts.rules]]
+name = \"warn\"
+below = 1.1
+severity = \"warning\"
+";
+        let issues = issues(source, Format::Toml);
+        assert_eq!(issues.len(), 1, "{:?}", issues);
+        assert_eq!(issues[0].0, Some(10));
+        assert!(issues[0].1.contains("defined twice"));
+    }
+
+    #[test]
+    fn huge_backoff_does_not_overflow() {
+        let source = format!("[pyth]\nmax_backoff_secs = {}\n", i64::MAX);
+        assert!(issues(&source, Format::Toml).is_empty());
+    }
+
+    #[test]
+    fn rejects_half_set_smtp_credentials() {
+        let source = "\
+[alerts.sinks.mail]
+type = \"email\"
+host = \"localhost\"
+username = \"monitor\"
+from = \"monitor@example.com\"
+to = [\"ops@example.com\"]
+";
+        let issues = issues(source, Format::Toml);
+        assert_eq!(issues.len(), 1, "{:?}", issues);
+        assert_eq!(issues[0].0, Some(1));
+        assert!(issues[0].1.contains("`username` and `password`"));
+    }
+
+    #[test]
+    fn validates_sections_merged_after_parsing() {
+        let mut config = MonitorConfig::parse("", Format::Toml).unwrap();
+        assert!(config.validate("merged").is_ok());
+
+        config.rpc = Format::Toml.parse("health_check_secs = 0\n").unwrap();
+        let err = config.validate("merged").unwrap_err().to_string();
+        assert!(err.contains("merged"), "{}", err);
+        assert!(
+            err.contains("`health_check_secs` must be greater than zero"),
+            "{}",
+            err
+        );
+    }
+}
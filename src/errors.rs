@@ -1,10 +1,82 @@
-use sui_sdk::error::Error;
+use crate::config::validation::ConfigIssues;
+use sui_sdk::{error::Error, types::base_types::ObjectID};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum Errors {
     #[error(transparent)]
     SuiError(#[from] Error),
-    #[error("Failed to parse str into SuiFeed")]
-    SuiFeedIdParsingError,
+    #[error("Invalid Pyth feed ID `{0}`")]
+    InvalidFeedId(String),
+    #[error("No Pyth feed known for coin `{0}`, add it to [suilend.coin_feeds]")]
+    UnknownCoin(String),
+    #[error("Failed to parse Move object field `{0}`")]
+    MoveObjectParsingError(&'static str),
+    #[error("Failed to parse field `{field}` of object {object_id}")]
+    ObjectFieldParsingError {
+        object_id: ObjectID,
+        field: &'static str,
+    },
+    #[error(transparent)]
+    JsonError(#[from] serde_json::Error),
+    #[error("Reserve {0} not found in lending market")]
+    ReserveNotFound(u64),
+    #[error("Lending market {0} is not loaded")]
+    MarketNotLoaded(ObjectID),
+    #[error("No coin metadata for `{0}`")]
+    NoCoinMetadata(String),
+    #[error("No reserve for coin {0}")]
+    NoReserveForCoin(String),
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+    #[error(transparent)]
+    HttpError(#[from] reqwest::Error),
+    #[error(transparent)]
+    SmtpError(#[from] lettre::transport::smtp::Error),
+    #[error(transparent)]
+    EmailError(#[from] lettre::error::Error),
+    #[error(transparent)]
+    EmailAddressError(#[from] lettre::address::AddressError),
+    #[error("Email sink sets only one of `username` and `password`")]
+    IncompleteSmtpCredentials,
+    #[error("Alert sink `{0}` is not defined")]
+    UnknownSink(String),
+    #[error("Failed to parse Pyth price `{0}`")]
+    PriceParsingError(String),
+    #[error("Malformed update for feed {feed}: {source}")]
+    InvalidPriceUpdate { feed: String, source: Box<Errors> },
+    #[error("Malformed Hermes message: {0}")]
+    InvalidStreamMessage(serde_json::Error),
+    #[error("Price stream disconnected: {0}")]
+    StreamDisconnected(String),
+    #[error("Negative price {0} cannot value a position")]
+    NegativePrice(i64),
+    #[error("Arithmetic overflow")]
+    Overflow,
+    #[error("Division by zero")]
+    DivisionByZero,
+    #[error("No Sui RPC endpoint is reachable")]
+    NoHealthyRpcEndpoint,
+    #[error("Nothing to watch, pass --address, --obligation or --watch")]
+    NoWatchTargets,
+    #[error("Malformed {event} in transaction {tx_digest}: {source}")]
+    InvalidEvent {
+        event: String,
+        tx_digest: String,
+        source: Box<Errors>,
+    },
+    #[error("Object {0} not found")]
+    ObjectNotFound(ObjectID),
+    #[error("{type_name} {object_id} has an unexpected layout: {reason}")]
+    UnexpectedMoveLayout {
+        object_id: ObjectID,
+        type_name: &'static str,
+        reason: String,
+    },
+    #[error("Invalid Move struct type `{0}`")]
+    InvalidStructTag(String),
+    #[error("Invalid object id `{0}`")]
+    InvalidObjectId(String),
+    #[error("Invalid config {path}:{issues}")]
+    InvalidConfig { path: String, issues: ConfigIssues },
 }
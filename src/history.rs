@@ -0,0 +1,84 @@
+use crate::{
+    pyth::{
+        prices::PythPrice,
+        types::{FeedId, PriceUpdate},
+    },
+    suilend::{health::ObligationHealth, interest::unix_now},
+};
+use dashmap::DashMap;
+use std::{collections::VecDeque, sync::LazyLock};
+use sui_sdk::types::base_types::ObjectID;
+
+/// Price of every feed over the retention window, one sample per second at most
+pub static PRICE_HISTORY: LazyLock<DashMap<FeedId, History<PythPrice>>> =
+    LazyLock::new(DashMap::new);
+
+/// Health ratio of every tracked obligation over the retention window
+pub static HEALTH_HISTORY: LazyLock<DashMap<ObjectID, History<f64>>> = LazyLock::new(DashMap::new);
+
+/// Timestamped samples (unix seconds) of the last `window` seconds, oldest first
+#[derive(Debug)]
+pub struct History<T> {
+    samples: VecDeque<(u64, T)>,
+}
+
+impl<T> Default for History<T> {
+    fn default() -> Self {
+        Self {
+            samples: VecDeque::new(),
+        }
+    }
+}
+
+impl<T: Copy> History<T> {
+    /// Adds a sample, replacing one from the same second, and drops what fell out of `window`
+    pub fn record(&mut self, at: u64, value: T, window: u64) {
+        match self.samples.back_mut() {
+            Some(last) if last.0 == at => last.1 = value,
+            Some(last) if last.0 > at => return,
+            _ => self.samples.push_back((at, value)),
+        }
+        while self
+            .samples
+            .front()
+            .is_some_and(|(t, _)| t.saturating_add(window) < at)
+        {
+            self.samples.pop_front();
+        }
+    }
+
+    /// The latest sample taken at or before `at`
+    pub fn at(&self, at: u64) -> Option<T> {
+        self.samples
+            .iter()
+            .rev()
+            .find(|(t, _)| *t <= at)
+            .map(|(_, value)| *value)
+    }
+
+    /// Samples taken in `[from, to]`
+    pub fn between(&self, from: u64, to: u64) -> Vec<(u64, T)> {
+        self.samples
+            .iter()
+            .filter(|(t, _)| (from..=to).contains(t))
+            .copied()
+            .collect()
+    }
+}
+
+pub fn record_price(update: &PriceUpdate, window: u64) {
+    PRICE_HISTORY.entry(update.feed).or_default().record(
+        update.price.publish_time.max(0) as u64,
+        update.price,
+        window,
+    );
+}
+
+pub fn record_health(health: &ObligationHealth, window: u64) {
+    if let Some(ratio) = health.health_ratio {
+        HEALTH_HISTORY
+            .entry(health.obligation_id)
+            .or_default()
+            .record(unix_now(), ratio, window);
+    }
+}
@@ -1,135 +1,226 @@
-use clap::Parser;
+use alerts::notifier::AlertsConfig;
+use clap::{Parser, Subcommand};
+use config::{Format, MonitorConfig};
 use errors::Errors;
-use pyth::{
-    prices::LATEST_PRICES,
-    types::{ChainFeedId, PythClient, PythSSE, SuiFeedId},
+use futures::StreamExt;
+use monitor::Monitor;
+use rpc::{EndpointConfig, Network, RpcConfig};
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
 };
-use sui_sdk::types::base_types::SuiAddress;
-use suilend::objects::{Obligation, SuilendAccount};
-// use suilend::{
-//     objects::SuilendAccount,
-//     types::{Asset, Deposit, Loan, Position},
-// };
+use tokio::sync::{broadcast, mpsc};
+use tracing::{debug, warn};
+use tracing_subscriber::EnvFilter;
+use watch::{WatchList, WatchTarget};
+pub mod alerts;
+pub mod config;
 pub mod errors;
+pub mod history;
+pub mod monitor;
 pub mod pyth;
+pub mod rpc;
 pub mod suilend;
+pub mod watch;
 
 #[derive(Parser)]
-#[command(version, about, long_about = None)]
+#[command(version, about, long_about = None, args_conflicts_with_subcommands = true)]
 pub struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// TOML, YAML or JSON file configuring the whole monitor; flags below override it
     #[arg(short, long)]
-    address: SuiAddress,
+    config: Option<PathBuf>,
+    /// Wallet whose obligations to watch, as `[label=]0x...`; may be repeated
+    #[arg(short, long = "address", value_parser = WatchTarget::parse_address)]
+    addresses: Vec<WatchTarget>,
+    /// Obligation object to watch directly, as `[label=]0x...`; may be repeated
+    #[arg(long = "obligation", value_parser = WatchTarget::parse_obligation)]
+    obligations: Vec<WatchTarget>,
+    /// Watch list of labelled addresses and obligation IDs
+    #[arg(long)]
+    watch: Option<PathBuf>,
+    /// Alert sinks, per-obligation routes and health thresholds
+    #[arg(long)]
+    alerts: Option<PathBuf>,
+    /// Sui network, RPC endpoints, rate limits and failover strategy
+    #[arg(long)]
+    rpc_config: Option<PathBuf>,
+    #[arg(long, value_enum)]
+    network: Option<Network>,
+    /// Sui RPC endpoint, may be repeated; overrides the configured endpoints
+    #[arg(long = "rpc-url")]
+    rpc_urls: Vec<String>,
+    #[arg(long)]
+    hermes_url: Option<String>,
+    /// `tracing` filter directives, e.g. `debug`
+    #[arg(long)]
+    log_level: Option<String>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Inspect configuration files
+    Config {
+        #[command(subcommand)]
+        action: ConfigCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Validate a config file and report every problem with its line number
+    Check { path: PathBuf },
+}
+
+impl Cli {
+    /// Layers the side files and CLI flags over the config file, then validates the result
+    fn apply(&self, config: &mut MonitorConfig) -> Result<(), Errors> {
+        if let Some(path) = self.watch.as_deref() {
+            config.targets.extend(WatchList::from_file(path)?.targets);
+        }
+        config.targets.extend(self.addresses.iter().cloned());
+        config.targets.extend(self.obligations.iter().cloned());
+        if let Some(path) = self.alerts.as_deref() {
+            config.alerts = AlertsConfig::from_file(path)?;
+        }
+        if let Some(path) = self.rpc_config.as_deref() {
+            config.rpc = RpcConfig::from_file(path)?;
+        }
+        if let Some(network) = self.network {
+            config.rpc.network = network;
+        }
+        if !self.rpc_urls.is_empty() {
+            config.rpc.endpoints = self
+                .rpc_urls
+                .iter()
+                .map(|url| EndpointConfig {
+                    url: url.clone(),
+                    rate_limit: None,
+                })
+                .collect();
+        }
+        if let Some(hermes_url) = self.hermes_url.as_ref() {
+            config.pyth.hermes_url = hermes_url.clone();
+        }
+        if let Some(level) = self.log_level.as_ref() {
+            config.logging.level = level.clone();
+        }
+        let origin = self
+            .config
+            .as_deref()
+            .map_or("command line".to_string(), |path| {
+                format!("{} with overrides", path.display())
+            });
+        config.validate(&origin)
+    }
+}
+
+fn check_config(path: &Path) -> Result<(), Errors> {
+    let source = std::fs::read_to_string(path)?;
+    let format = Format::from_path(path);
+    match MonitorConfig::parse(&source, format) {
+        Ok(config) => {
+            println!(
+                "{}: OK ({}, {} targets, {} sinks, {} rules)",
+                path.display(),
+                format,
+                config.targets.len(),
+                config.alerts.sinks.len(),
+                config.alerts.rules.len()
+            );
+            Ok(())
+        }
+        Err(issues) => {
+            eprintln!(
+                "{}: {} problem(s){}",
+                path.display(),
+                issues.0.len(),
+                issues
+            );
+            std::process::exit(1);
+        }
+    }
 }
 
 #[tokio::main]
 pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let address = Cli::parse().address;
+    let cli = Cli::parse();
+    if let Some(Command::Config {
+        action: ConfigCommand::Check { path },
+    }) = cli.command.as_ref()
+    {
+        return Ok(check_config(path)?);
+    }
 
-    // This should get us the feeds we need to track
-    // after unpacking the info from the move object
-    // todo: unpack relevant data from second object's fields
-    // Obligation Cap Obj -> Obligation Obj
-    let obligations = SuilendAccount::get_suilend_accounts(address).await?;
-    let feeds = obligations.iter().fold(Vec::new(), |mut acc, e| {
-        let ids = e
-            .borrows
-            .iter()
-            .zip(e.deposits.iter())
-            .map(|(borrow, deposit)| {
-                let borrow_name = borrow
-                    .coin_type
-                    .name
-                    .split("::")
-                    .last()
-                    .unwrap()
-                    .to_lowercase();
-                println!("{}", &borrow_name);
+    let mut config = cli
+        .config
+        .as_deref()
+        .map(MonitorConfig::from_file)
+        .transpose()?
+        .unwrap_or_default();
+    cli.apply(&mut config)?;
 
-                let deposit_name = deposit
-                    .coin_type
-                    .name
-                    .split("::")
-                    .last()
-                    .unwrap()
-                    .to_lowercase();
-                println!("{}", deposit_name);
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::try_new(&config.logging.level)?)
+        .with_ansi(config.logging.ansi)
+        .init();
 
-                let id1 = ChainFeedId::Sui(SuiFeedId::from_name(&borrow_name).unwrap());
-                let id2 = ChainFeedId::Sui(SuiFeedId::from_name(&deposit_name).unwrap());
-                vec![id1, id2]
-            })
-            .flatten()
-            .collect::<Vec<ChainFeedId>>();
+    let mut monitor = Monitor::start(config).await?;
 
-        acc.extend_from_slice(&ids);
-        acc
+    let mut price_log = monitor.prices().subscribe();
+    tokio::spawn(async move {
+        while let Some(update) = price_log.next().await {
+            debug!("{}: {}", pyth::registry::name(&update.feed), update.price);
+        }
     });
 
-    // replace hard coded feeds
-    let client = PythClient::new();
-    client
-        .stream_price_feeds(feeds, |event| async move {
-            let json: PythSSE = serde_json::from_str(&event).unwrap();
-
-            json.parsed.iter().for_each(|e| {
-                let chain = ChainFeedId::from_str(&e.id).unwrap();
-                let price = e.price.price.parse::<u128>().unwrap();
-                println!("{:?}{}", chain, e.price);
-                LATEST_PRICES.insert(chain, price);
-            });
+    let mut reloads = cli.config.clone().map(|path| {
+        config::reload::spawn(path, move |path| {
+            let mut config = MonitorConfig::from_file(path)?;
+            cli.apply(&mut config)?;
+            Ok(config)
         })
-        .await;
+    });
+
+    let mut updates = monitor.prices().subscribe();
+    let mut changes = monitor.position_changes();
+    let mut rewards_check = rewards_interval(monitor.rewards_interval());
+    loop {
+        tokio::select! {
+            update = updates.next() => match update {
+                Some(update) => monitor.on_price(update),
+                None => break,
+            },
+            change = changes.recv() => match change {
+                Ok(event) => monitor.on_position_change(event),
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    warn!("Missed {} position changes", missed);
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            Some(event) = monitor.next_event() => monitor.on_event(event),
+            _ = rewards_check.tick() => monitor.on_rewards_check(),
+            Some(config) = next_reload(&mut reloads) => {
+                monitor.reload(config).await;
+                if monitor.rewards_interval() != rewards_check.period() {
+                    rewards_check = rewards_interval(monitor.rewards_interval());
+                }
+            }
+        }
+    }
 
     Ok(())
 }
 
-// let client = PythClient::new();
-// let feed = ChainFeedId::Sui(SuiFeedId::Sui);
-// let (tx, mut rx) = channel(1000);
-//
-// tokio::spawn(async move {
-//     while let Some((price, decimals)) = rx.recv().await {
-//         let deposit = Deposit {
-//             asset: Asset::Sui,
-//             amount: 2200,
-//         };
-//         let loan = Loan {
-//             asset: Asset::WUSDC,
-//             amount: 2206,
-//         };
-//
-//         let position = Position { loan, deposit };
-//         let position_deposit_value = price * position.deposit.amount;
-//         let equity = position_deposit_value - 220600000000;
-//         let liq_price = position_deposit_value * 75 / 100;
-//         println!(
-//             "\nLiq % Away: {}",
-//             ((liq_price / position.loan.amount) as f64 / 1000000.0) - 75.0
-//         );
-//         println!("Liq Threshold: {}", liq_price);
-//         println!("Weighted Borrow: {}", position.loan.amount);
-//         println!("Deposit Value: {}", position_deposit_value);
-//         println!("Equity: {}\n", equity);
-//     }
-// });
-//
-// client
-//     .stream_price_feed(feed, |x| {
-//         let tx = tx.clone();
-//         async move {
-//             let json: PythSSE = serde_json::from_str(&x).unwrap();
-//             for json in json.parsed.iter() {
-//                 let spot_price: u128 = json.price.price.parse::<u128>().unwrap();
-//                 let decimals = json.price.expo;
-//                 let ema_price = json.ema_price.price.parse::<u128>().unwrap();
-//                 tx.send((
-//                     std::cmp::min(spot_price, ema_price),
-//                     decimals.unsigned_abs() as u32,
-//                 ))
-//                 .await
-//                 .unwrap()
-//             }
-//         }
-//     })
-//     .await;
-//
+/// Skips the immediate first tick, prices are still arriving at startup
+fn rewards_interval(period: Duration) -> tokio::time::Interval {
+    tokio::time::interval_at(tokio::time::Instant::now() + period, period)
+}
+
+async fn next_reload(reloads: &mut Option<mpsc::Receiver<MonitorConfig>>) -> Option<MonitorConfig> {
+    match reloads.as_mut() {
+        Some(reloads) => reloads.recv().await,
+        None => std::future::pending().await,
+    }
+}
@@ -0,0 +1,394 @@
+use crate::{
+    alerts::notifier::{Alert, AlertRouter, AlertsConfig},
+    config::MonitorConfig,
+    errors::Errors,
+    history::{record_health, record_price, HEALTH_HISTORY},
+    pyth::{
+        prices::{LATEST_EMA_PRICES, LATEST_PRICES},
+        registry,
+        stream::PriceBroadcast,
+        subscriptions::FeedSubscriptions,
+        types::{PriceUpdate, PythClient},
+    },
+    rpc::RpcPool,
+    suilend::{
+        coins,
+        events::{EventIngestor, EventKind, ReserveRefreshEvent, SuilendEvent},
+        health::{evaluate_feed, ObligationHealth, TRACKED_OBLIGATIONS},
+        interest::unix_now,
+        plan::FeedPlan,
+        postmortem::PostMortem,
+        reserves::Markets,
+        rewards::RewardsReport,
+        sync::{ObligationSync, PositionEvent},
+    },
+    watch::ActiveWatch,
+};
+use std::{
+    collections::{BTreeMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
+use sui_sdk::types::base_types::ObjectID;
+use tokio::sync::broadcast;
+use tracing::{debug, info, info_span, warn};
+
+/// Everything that changes while the process runs, so a new config can be applied in place
+pub struct Monitor {
+    /// The config in effect
+    config: MonitorConfig,
+    /// The last config reloaded, applied or not, so each change is reported once
+    last_seen: MonitorConfig,
+    rpc: Arc<RpcPool>,
+    markets: Markets,
+    alerts: Option<AlertRouter>,
+    watch: ActiveWatch,
+    subscriptions: FeedSubscriptions,
+    sync: ObligationSync,
+    events: Option<EventIngestor>,
+    plan: FeedPlan,
+    /// Obligations already alerted about their unclaimed rewards, until they claim
+    reward_alerts: HashSet<ObjectID>,
+}
+
+impl Monitor {
+    pub async fn start(config: MonitorConfig) -> Result<Self, Errors> {
+        if config.targets.is_empty() {
+            return Err(Errors::NoWatchTargets);
+        }
+        let rpc = RpcPool::connect(&config.rpc).await?;
+        let alerts = Self::router(&config.alerts)?;
+        registry::load(&config.pyth).await;
+        coins::install(&config.suilend.coin_feeds);
+
+        // Markets are loaded as the watched obligations turn up in them
+        let markets = Markets::default();
+        let mut watch = ActiveWatch::default();
+        watch
+            .sync(&config.targets, &rpc, &config.suilend, &markets)
+            .await;
+        for (lending_market_id, reserves) in markets.iter() {
+            for reserve in reserves.iter() {
+                if let Err(e) = reserve.feed() {
+                    warn!(
+                        "Reserve {} of market {} cannot be priced: {}",
+                        reserve.array_index, lending_market_id, e
+                    );
+                }
+            }
+        }
+
+        let subscriptions = FeedSubscriptions::new(
+            PythClient::with_config(config.pyth.stream_config()),
+            config.pyth.broadcast_capacity,
+        );
+
+        let sync =
+            ObligationSync::spawn(rpc.clone(), markets.clone(), &config.sync, &config.suilend);
+        let events = if config.events.enabled {
+            Some(EventIngestor::spawn(
+                rpc.clone(),
+                markets.clone(),
+                &config.suilend,
+                &config.events,
+            )?)
+        } else {
+            None
+        };
+
+        let mut monitor = Self {
+            last_seen: config.clone(),
+            config,
+            rpc,
+            markets,
+            alerts,
+            watch,
+            subscriptions,
+            sync,
+            events,
+            plan: FeedPlan::default(),
+            reward_alerts: HashSet::new(),
+        };
+        monitor.sync_feeds();
+        Ok(monitor)
+    }
+
+    /// Re-plans feeds from the current positions and subscribes to the difference
+    fn sync_feeds(&mut self) {
+        // Valuing unclaimed rewards needs their coins' prices too
+        let include_rewards =
+            self.config.pyth.reward_feeds || self.config.rewards.alert_above_usd.is_some();
+        let plan = FeedPlan::build(&self.markets, include_rewards);
+        if plan != self.plan {
+            info!("Feed plan: {}", plan);
+            self.subscriptions.sync(&plan.feeds());
+            self.plan = plan;
+        }
+    }
+
+    fn router(config: &AlertsConfig) -> Result<Option<AlertRouter>, Errors> {
+        if config.is_empty() {
+            Ok(None)
+        } else {
+            AlertRouter::new(config.clone()).map(Some)
+        }
+    }
+
+    pub fn prices(&self) -> &PriceBroadcast {
+        self.subscriptions.broadcast()
+    }
+
+    pub fn position_changes(&self) -> broadcast::Receiver<PositionEvent> {
+        self.sync.subscribe()
+    }
+
+    /// Waits forever when event ingestion is disabled
+    pub async fn next_event(&mut self) -> Option<SuilendEvent> {
+        match self.events.as_mut() {
+            Some(events) => events.recv().await,
+            None => std::future::pending().await,
+        }
+    }
+
+    /// Re-syncs the obligation an event touched, and raises the alarm on liquidations
+    pub fn on_event(&self, event: SuilendEvent) {
+        let Some(obligation_id) = event.kind.obligation_id() else {
+            if let EventKind::ReserveRefresh(refresh) = &event.kind {
+                self.on_reserve_refresh(&event, refresh);
+            }
+            return;
+        };
+        let label = TRACKED_OBLIGATIONS
+            .get(&obligation_id)
+            .map(|tracked| tracked.label.clone())
+            .unwrap_or_default();
+        info!("[{}] {}: {}", label, obligation_id, event.kind);
+
+        if let EventKind::Liquidate(liquidation) = &event.kind {
+            let report = self
+                .markets
+                .get(&liquidation.lending_market_id)
+                .and_then(|reserves| {
+                    PostMortem::new(
+                        &label,
+                        &event,
+                        liquidation,
+                        &reserves,
+                        self.config.postmortem.history_secs,
+                    )
+                });
+            let alert = match report {
+                Ok(report) => {
+                    match report.write(&self.config.postmortem.dir) {
+                        Ok(path) => info!("Wrote post-mortem to {}", path.display()),
+                        Err(e) => warn!("Failed to write post-mortem: {}", e),
+                    }
+                    Alert::from_post_mortem(&report)
+                }
+                Err(e) => {
+                    warn!("Cannot build post-mortem for {}: {}", obligation_id, e);
+                    Alert::from_liquidation(&label, liquidation, event.sender)
+                }
+            };
+            match self.alerts.as_ref() {
+                Some(alerts) => alerts.dispatch(alert),
+                None => info!("{}", alert),
+            }
+        }
+        self.sync.refresh(obligation_id);
+    }
+
+    /// Moves a watched reserve, and the borrows accruing against it, to its on-chain refresh
+    fn on_reserve_refresh(&self, event: &SuilendEvent, refresh: &ReserveRefreshEvent) {
+        let timestamp_s = event
+            .timestamp_ms
+            .map(|ms| ms / 1000)
+            .unwrap_or_else(unix_now);
+        let reserve = match self.markets.apply_refresh(refresh, timestamp_s) {
+            Ok(Some(reserve)) => reserve,
+            Ok(None) => return,
+            Err(e) => {
+                warn!("Skipping {}: {}", event.kind, e);
+                return;
+            }
+        };
+        debug!("{}", event.kind);
+        for mut tracked in TRACKED_OBLIGATIONS.iter_mut() {
+            if tracked.obligation.lending_market_id != refresh.lending_market_id {
+                continue;
+            }
+            if let Err(e) = tracked.on_reserve_refresh(&reserve) {
+                warn!("Cannot re-project borrows of {}: {}", tracked.id, e);
+            }
+        }
+    }
+
+    pub fn on_position_change(&mut self, event: PositionEvent) {
+        match self.alerts.as_ref() {
+            Some(alerts) => alerts.dispatch(Alert::from_position_change(&event)),
+            None => info!("{}", event),
+        }
+        // A new coin needs its feed, a closed position no longer does
+        self.sync_feeds();
+
+        let health = TRACKED_OBLIGATIONS
+            .get(&event.obligation_id)
+            .map(|tracked| tracked.evaluate());
+        match health {
+            Some(Ok(health)) => {
+                record_health(&health, self.config.postmortem.history_secs);
+                if let Some(alerts) = self.alerts.as_ref() {
+                    alerts.on_health(&health);
+                }
+                let _watch = info_span!("watch", label = %health.label).entered();
+                info!("{}", health);
+            }
+            Some(Err(e)) => warn!("Skipping health of {}: {}", event.obligation_id, e),
+            None => {}
+        }
+    }
+
+    pub fn on_price(&self, update: PriceUpdate) {
+        if update.price.is_negative() {
+            warn!(
+                "Ignoring negative price for {}",
+                registry::name(&update.feed)
+            );
+            return;
+        }
+        LATEST_PRICES.insert(update.feed, update.price);
+        if !update.ema_price.is_negative() {
+            LATEST_EMA_PRICES.insert(update.feed, update.ema_price);
+        }
+        let window = self.config.postmortem.history_secs;
+        record_price(&update, window);
+
+        let mut by_label: BTreeMap<String, Vec<ObligationHealth>> = BTreeMap::new();
+        for health in evaluate_feed(&update.feed) {
+            record_health(&health, window);
+            if let Some(alerts) = self.alerts.as_ref() {
+                alerts.on_health(&health);
+            }
+            by_label
+                .entry(health.label.clone())
+                .or_default()
+                .push(health);
+        }
+        for (label, healths) in by_label {
+            let _watch = info_span!("watch", label = %label).entered();
+            for health in healths {
+                info!("{}", health);
+            }
+        }
+    }
+
+    pub fn rewards_interval(&self) -> Duration {
+        Duration::from_secs(self.config.rewards.interval_secs)
+    }
+
+    /// Logs every obligation's unclaimed rewards and yields, alerting once they pass the threshold
+    pub fn on_rewards_check(&mut self) {
+        let now_ms = unix_now() * 1000;
+        let threshold = self.config.rewards.alert_above_usd;
+        for tracked in TRACKED_OBLIGATIONS.iter() {
+            let report = self
+                .markets
+                .get(&tracked.obligation.lending_market_id)
+                .and_then(|reserves| RewardsReport::new(&tracked, &reserves, now_ms));
+            let report = match report {
+                Ok(report) => report,
+                Err(e) => {
+                    warn!("Skipping rewards of {}: {}", tracked.id, e);
+                    continue;
+                }
+            };
+            info!("{}", report);
+
+            let Some(threshold) = threshold else {
+                continue;
+            };
+            if report.unclaimed_usd() < threshold {
+                // Re-arms once the rewards have been claimed
+                self.reward_alerts.remove(&report.obligation_id);
+            } else if self.reward_alerts.insert(report.obligation_id) {
+                let alert = Alert::from_rewards(&report);
+                match self.alerts.as_ref() {
+                    Some(alerts) => alerts.dispatch(alert),
+                    None => info!("{}", alert),
+                }
+            }
+        }
+    }
+
+    /// Applies watch targets, alerting and rewards from `config` live; other sections need a restart
+    pub async fn reload(&mut self, config: MonitorConfig) {
+        if config == self.last_seen {
+            return;
+        }
+        let last_seen = std::mem::replace(&mut self.last_seen, config.clone());
+        if config.targets.is_empty() {
+            warn!("Rejected config change: {}", Errors::NoWatchTargets);
+            return;
+        }
+        // Compared with the last config seen, so each change is reported once
+        for (section, changed) in [
+            ("rpc", config.rpc != last_seen.rpc),
+            ("pyth", config.pyth != last_seen.pyth),
+            ("suilend", config.suilend != last_seen.suilend),
+            ("sync", config.sync != last_seen.sync),
+            ("events", config.events != last_seen.events),
+            ("postmortem", config.postmortem != last_seen.postmortem),
+            ("logging", config.logging != last_seen.logging),
+        ] {
+            if changed {
+                warn!("Changes to [{}] take effect after a restart", section);
+            }
+        }
+
+        if config.alerts != self.config.alerts {
+            let applied = match self.alerts.as_mut() {
+                Some(router) if !config.alerts.is_empty() => {
+                    router.reconfigure(config.alerts.clone())
+                }
+                _ => Self::router(&config.alerts).map(|alerts| self.alerts = alerts),
+            };
+            match applied {
+                Ok(()) => {
+                    info!("Applied new alerting config");
+                    self.config.alerts = config.alerts;
+                }
+                Err(e) => warn!("Rejected alerting change: {}", e),
+            }
+        }
+
+        if config.rewards != self.config.rewards {
+            info!("Applied new rewards config");
+            self.config.rewards = config.rewards;
+            self.sync_feeds();
+        }
+
+        if config.targets != self.config.targets {
+            // Reserves may have been added or re-rated since startup
+            self.markets.refresh(&self.rpc).await;
+            let dropped = self
+                .watch
+                .sync(
+                    &config.targets,
+                    &self.rpc,
+                    &self.config.suilend,
+                    &self.markets,
+                )
+                .await;
+            for id in dropped.iter() {
+                info!("Stopped watching obligation {}", id);
+                HEALTH_HISTORY.remove(id);
+                self.reward_alerts.remove(id);
+                if let Some(alerts) = self.alerts.as_ref() {
+                    alerts.forget(id);
+                }
+            }
+            self.sync_feeds();
+            self.config.targets = config.targets;
+        }
+    }
+}
@@ -1,2 +1,5 @@
 pub mod prices;
+pub mod registry;
+pub mod stream;
+pub mod subscriptions;
 pub mod types;
@@ -1,5 +1,205 @@
-use super::types::ChainFeedId;
+use super::types::{FeedId, Price};
+use crate::errors::Errors;
 use dashmap::DashMap;
-use std::sync::LazyLock;
+use move_core_types::u256::U256;
+use std::{fmt, sync::LazyLock};
 
-pub static LATEST_PRICES: LazyLock<DashMap<ChainFeedId, u128>> = LazyLock::new(|| DashMap::new());
+pub static LATEST_PRICES: LazyLock<DashMap<FeedId, PythPrice>> = LazyLock::new(DashMap::new);
+
+/// Hermes' EMA price for each feed, which Suilend keeps as the reserve's `smoothed_price`
+pub static LATEST_EMA_PRICES: LazyLock<DashMap<FeedId, PythPrice>> = LazyLock::new(DashMap::new);
+
+/// Suilend `Decimal`s are scaled by 10^18
+pub const WAD_DECIMALS: u32 = 18;
+
+pub fn pow10(exp: u32) -> Result<U256, Errors> {
+    (0..exp).try_fold(U256::one(), |acc, _| {
+        acc.checked_mul(U256::from(10u8)).ok_or(Errors::Overflow)
+    })
+}
+
+/// `a * b / c` without intermediate rounding
+pub fn mul_div(a: U256, b: U256, c: U256) -> Result<U256, Errors> {
+    a.checked_mul(b)
+        .and_then(|product| product.checked_div(c))
+        .ok_or(Errors::Overflow)
+}
+
+/// A Pyth price: `mantissa * 10^expo`, with confidence in the same scale
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PythPrice {
+    pub mantissa: i64,
+    pub conf: u64,
+    pub expo: i32,
+    pub publish_time: i64,
+}
+
+impl PythPrice {
+    pub fn new(mantissa: i64, conf: u64, expo: i32, publish_time: i64) -> Self {
+        Self {
+            mantissa,
+            conf,
+            expo,
+            publish_time,
+        }
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.mantissa < 0
+    }
+
+    fn unsigned_mantissa(&self) -> Result<U256, Errors> {
+        u64::try_from(self.mantissa)
+            .map(U256::from)
+            .map_err(|_| Errors::NegativePrice(self.mantissa))
+    }
+
+    /// Rescales `value * 10^self.expo` to `value' * 10^-decimals`, truncating extra precision
+    fn rescale(&self, value: U256, decimals: u32) -> Result<U256, Errors> {
+        let shift = decimals as i64 + self.expo as i64;
+        if shift >= 0 {
+            value
+                .checked_mul(pow10(shift as u32)?)
+                .ok_or(Errors::Overflow)
+        } else {
+            value
+                .checked_div(pow10(shift.unsigned_abs() as u32)?)
+                .ok_or(Errors::Overflow)
+        }
+    }
+
+    /// Price as an 18-decimal WAD, the scale of Suilend's `Decimal`
+    pub fn to_wad(&self) -> Result<U256, Errors> {
+        self.rescale(self.unsigned_mantissa()?, WAD_DECIMALS)
+    }
+
+    /// Confidence interval as an 18-decimal WAD
+    pub fn conf_to_wad(&self) -> Result<U256, Errors> {
+        self.rescale(U256::from(self.conf), WAD_DECIMALS)
+    }
+
+    /// WAD value of `amount` raw token units of a coin with `decimals` decimals
+    pub fn value_of(&self, amount: U256, decimals: u8) -> Result<U256, Errors> {
+        mul_div(amount, self.to_wad()?, pow10(decimals as u32)?)
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        self.mantissa as f64 * 10f64.powi(self.expo)
+    }
+}
+
+impl TryFrom<&Price> for PythPrice {
+    type Error = Errors;
+
+    fn try_from(price: &Price) -> Result<Self, Self::Error> {
+        Ok(Self {
+            mantissa: price
+                .price
+                .parse::<i64>()
+                .map_err(|_| Errors::PriceParsingError(price.price.clone()))?,
+            conf: price
+                .conf
+                .parse::<u64>()
+                .map_err(|_| Errors::PriceParsingError(price.conf.clone()))?,
+            expo: price.expo as i32,
+            publish_time: price.publish_time,
+        })
+    }
+}
+
+impl fmt::Display for PythPrice {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sign = if self.is_negative() { "-" } else { "" };
+        let value = self.mantissa.unsigned_abs();
+        if self.expo >= 0 {
+            let scaled = 10u128
+                .checked_pow(self.expo.unsigned_abs())
+                .and_then(|scale| (value as u128).checked_mul(scale));
+            return match scaled {
+                Some(scaled) => write!(f, "{}{}", sign, scaled),
+                None => write!(f, "{}{}e{}", sign, value, self.expo),
+            };
+        }
+
+        let expo_abs = self.expo.unsigned_abs();
+        match 10u128.checked_pow(expo_abs) {
+            Some(divisor) => write!(
+                f,
+                "{}{}.{:0width$}",
+                sign,
+                value as u128 / divisor,
+                value as u128 % divisor,
+                width = expo_abs as usize
+            ),
+            None => write!(f, "{}{}e{}", sign, value, self.expo),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wad(value: &str) -> U256 {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn scales_to_wad_for_either_exponent_sign() {
+        assert_eq!(
+            PythPrice::new(123_456_789, 0, -8, 0).to_wad().unwrap(),
+            wad("1234567890000000000")
+        );
+        assert_eq!(
+            PythPrice::new(5, 0, 2, 0).to_wad().unwrap(),
+            wad("500000000000000000000")
+        );
+        // Precision past 18 decimals is truncated
+        assert_eq!(
+            PythPrice::new(1_234_567, 0, -20, 0).to_wad().unwrap(),
+            wad("12345")
+        );
+    }
+
+    #[test]
+    fn rejects_negative_and_overflowing_prices() {
+        assert!(matches!(
+            PythPrice::new(-1, 0, -8, 0).to_wad(),
+            Err(Errors::NegativePrice(-1))
+        ));
+        assert!(matches!(
+            PythPrice::new(1, 0, 60, 0).to_wad(),
+            Err(Errors::Overflow)
+        ));
+        assert!(matches!(
+            PythPrice::new(1, 0, -8, 0).value_of(U256::max_value(), 9),
+            Err(Errors::Overflow)
+        ));
+    }
+
+    #[test]
+    fn values_raw_token_amounts() {
+        // 1.5 SUI of 9 decimals at $2
+        let price = PythPrice::new(200, 0, -2, 0);
+        assert_eq!(
+            price.value_of(U256::from(1_500_000_000u64), 9).unwrap(),
+            wad("3000000000000000000")
+        );
+        assert_eq!(price.value_of(U256::zero(), 9).unwrap(), U256::zero());
+    }
+
+    #[test]
+    fn displays_the_decimal_price() {
+        assert_eq!(PythPrice::new(12_345, 0, -2, 0).to_string(), "123.45");
+        assert_eq!(PythPrice::new(-5, 0, -3, 0).to_string(), "-0.005");
+        assert_eq!(PythPrice::new(7, 0, 3, 0).to_string(), "7000");
+        assert_eq!(PythPrice::new(-7, 0, 0, 0).to_string(), "-7");
+        // Out of range of an integer, so in scientific notation rather than padded
+        assert_eq!(PythPrice::new(7, 0, 40, 0).to_string(), "7e40");
+        assert_eq!(
+            PythPrice::new(7, 0, i32::MAX, 0).to_string(),
+            "7e2147483647"
+        );
+        assert_eq!(PythPrice::new(1, 0, -40, 0).to_string(), "1e-40");
+    }
+}
@@ -0,0 +1,133 @@
+use super::types::FeedId;
+use crate::{config::PythConfig, errors::Errors};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::{sync::LazyLock, time::Duration};
+use tracing::{info, warn};
+
+/// `/v2/price_feeds` output for the feeds Suilend used when this was last updated
+const SNAPSHOT: &str = include_str!("feeds.json");
+
+/// A stalled Hermes must not hold up startup, the snapshot is a fine fallback
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const FETCH_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Every known Pyth feed, seeded from the bundled snapshot
+pub static FEEDS: LazyLock<DashMap<FeedId, FeedMetadata>> = LazyLock::new(|| {
+    parse(SNAPSHOT)
+        .expect("bundled feed snapshot is valid")
+        .into_iter()
+        .map(|feed| (feed.id, feed))
+        .collect()
+});
+
+/// A feed's identity; also the shape of a user override in `[[pyth.feeds]]`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FeedMetadata {
+    pub id: FeedId,
+    /// e.g. `Crypto.SUI/USD`
+    pub symbol: String,
+    pub base: String,
+    #[serde(default = "FeedMetadata::default_quote_currency")]
+    pub quote_currency: String,
+    #[serde(default = "FeedMetadata::default_asset_type")]
+    pub asset_type: String,
+}
+
+impl FeedMetadata {
+    fn default_quote_currency() -> String {
+        "USD".to_string()
+    }
+
+    fn default_asset_type() -> String {
+        "Crypto".to_string()
+    }
+}
+
+#[derive(Deserialize)]
+struct HermesFeed {
+    id: FeedId,
+    attributes: HermesAttributes,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct HermesAttributes {
+    asset_type: String,
+    base: String,
+    quote_currency: String,
+    symbol: String,
+}
+
+impl From<HermesFeed> for FeedMetadata {
+    fn from(feed: HermesFeed) -> Self {
+        Self {
+            id: feed.id,
+            symbol: feed.attributes.symbol,
+            base: feed.attributes.base,
+            quote_currency: feed.attributes.quote_currency,
+            asset_type: feed.attributes.asset_type,
+        }
+    }
+}
+
+fn parse(json: &str) -> Result<Vec<FeedMetadata>, Errors> {
+    Ok(serde_json::from_str::<Vec<HermesFeed>>(json)?
+        .into_iter()
+        .map(FeedMetadata::from)
+        .collect())
+}
+
+pub async fn fetch(hermes_url: &str) -> Result<Vec<FeedMetadata>, Errors> {
+    let client = reqwest::Client::builder()
+        .connect_timeout(CONNECT_TIMEOUT)
+        .timeout(FETCH_TIMEOUT)
+        .build()?;
+    let body = client
+        .get(format!("{}/v2/price_feeds", hermes_url))
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    parse(&body)
+}
+
+/// Refreshes the registry from Hermes, if enabled, then applies the configured overrides
+pub async fn load(config: &PythConfig) {
+    if config.fetch_feeds {
+        match fetch(config.hermes_url.trim_end_matches('/')).await {
+            Ok(feeds) => {
+                info!("Loaded {} Pyth feeds from Hermes", feeds.len());
+                for feed in feeds {
+                    FEEDS.insert(feed.id, feed);
+                }
+            }
+            Err(e) => warn!("Using the bundled Pyth feed list: {}", e),
+        }
+    }
+    for feed in config.feeds.iter() {
+        FEEDS.insert(feed.id, feed.clone());
+    }
+}
+
+/// The USD feed for `base`, e.g. `SUI`, preferring the canonical `Crypto.<BASE>/USD` symbol
+pub fn by_base(base: &str) -> Option<FeedId> {
+    let symbol = format!("Crypto.{}/USD", base);
+    FEEDS
+        .iter()
+        .filter(|feed| {
+            feed.base.eq_ignore_ascii_case(base) && feed.quote_currency.eq_ignore_ascii_case("USD")
+        })
+        .min_by_key(|feed| (!feed.symbol.eq_ignore_ascii_case(&symbol), feed.id))
+        .map(|feed| feed.id)
+}
+
+/// A short human name for `feed`, its base asset when known
+pub fn name(feed: &FeedId) -> String {
+    match FEEDS.get(feed) {
+        Some(metadata) if !metadata.base.is_empty() => metadata.base.clone(),
+        _ => feed.to_string()[..8].to_string(),
+    }
+}
@@ -0,0 +1,313 @@
+use super::types::{FeedId, PriceUpdate, PythSSE};
+use crate::errors::Errors;
+use async_sse::{decode, Event};
+use futures::prelude::*;
+use rand::Rng;
+use reqwest::Client;
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+use stream::{BoxStream, StreamExt};
+use tokio::{
+    sync::{broadcast, mpsc},
+    task::AbortHandle,
+};
+use tracing::{info, warn};
+
+type UpdateSender = mpsc::Sender<Result<PriceUpdate, Errors>>;
+
+#[derive(Debug, Clone)]
+pub struct StreamConfig {
+    pub hermes_url: String,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Reconnect when no event arrives within this window
+    pub heartbeat_timeout: Duration,
+}
+
+impl StreamConfig {
+    pub const HERMES_URL: &'static str = "https://hermes.pyth.network";
+}
+
+impl Default for StreamConfig {
+    fn default() -> Self {
+        Self {
+            hermes_url: StreamConfig::HERMES_URL.to_string(),
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(60),
+            heartbeat_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Exponential backoff with full jitter
+struct Backoff {
+    current: Duration,
+    initial: Duration,
+    max: Duration,
+}
+
+impl Backoff {
+    fn new(config: &StreamConfig) -> Self {
+        Self {
+            current: config.initial_backoff,
+            initial: config.initial_backoff,
+            max: config.max_backoff,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.current = self.initial;
+    }
+
+    fn next_delay(&mut self) -> Duration {
+        let ceiling = self.current.as_millis() as u64;
+        self.current = (self.current * 2).min(self.max);
+        Duration::from_millis(rand::thread_rng().gen_range(ceiling / 2..=ceiling))
+    }
+}
+
+enum Disconnect {
+    /// Every consumer of the stream is gone
+    Unsubscribed,
+    Closed,
+    Stalled(Duration),
+    Decode(String),
+}
+
+impl fmt::Display for Disconnect {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Disconnect::Unsubscribed => write!(f, "no subscribers left"),
+            Disconnect::Closed => write!(f, "stream closed by Hermes"),
+            Disconnect::Stalled(after) => write!(f, "no events for {:?}", after),
+            Disconnect::Decode(e) => write!(f, "failed to decode event: {}", e),
+        }
+    }
+}
+
+pub struct MultiFeedStream {
+    feeds: Vec<FeedId>,
+    config: StreamConfig,
+}
+
+impl MultiFeedStream {
+    pub fn new(feeds: Vec<FeedId>) -> Self {
+        MultiFeedStream {
+            feeds,
+            config: StreamConfig::default(),
+        }
+    }
+
+    pub fn with_config(mut self, config: StreamConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    fn ids_query(&self) -> String {
+        self.feeds
+            .iter()
+            .map(|id| format!("ids[]={}", id))
+            .collect::<Vec<_>>()
+            .join("&")
+    }
+
+    /// Streams parsed updates, reconnecting with backoff whenever Hermes drops or stalls.
+    ///
+    /// Malformed messages and each disconnect are yielded as errors; the stream itself goes on.
+    ///
+    /// The connection is supervised by a background task that stops once the stream is dropped.
+    pub fn open_stream(self) -> impl Stream<Item = Result<PriceUpdate, Errors>> + Send + 'static {
+        let (sender, receiver) = mpsc::channel(1024);
+        if !self.feeds.is_empty() {
+            tokio::spawn(self.supervise(sender));
+        }
+        stream::unfold(receiver, |mut receiver| async move {
+            receiver.recv().await.map(|update| (update, receiver))
+        })
+    }
+
+    async fn supervise(self, sender: UpdateSender) {
+        let client = Client::new();
+        let url = format!(
+            "{}/v2/updates/price/stream?{}",
+            self.config.hermes_url,
+            self.ids_query()
+        );
+        let mut backoff = Backoff::new(&self.config);
+        let mut last_event: Option<Instant> = None;
+
+        loop {
+            let response = client
+                .get(&url)
+                .header("Content-Type", "text/event-stream")
+                .send()
+                .await
+                .and_then(|response| response.error_for_status());
+
+            match response {
+                Ok(response) => {
+                    if let Some(last) = last_event {
+                        warn!("Price stream gap of {:?}, backfilling", last.elapsed());
+                        if !self.backfill(&client, &sender).await {
+                            return;
+                        }
+                    }
+                    info!("Connected to Hermes for {} feeds", self.feeds.len());
+
+                    let stream = decode(
+                        response
+                            .bytes_stream()
+                            .map_err(std::io::Error::other)
+                            .into_async_read(),
+                    );
+                    match self
+                        .consume(stream, &sender, &mut last_event, &mut backoff)
+                        .await
+                    {
+                        Disconnect::Unsubscribed => return,
+                        reason => {
+                            let error = Errors::StreamDisconnected(reason.to_string());
+                            if sender.send(Err(error)).await.is_err() {
+                                return;
+                            }
+                        }
+                    }
+                }
+                Err(e) => warn!("Failed to connect to Hermes: {}", e),
+            }
+
+            if sender.is_closed() {
+                return;
+            }
+            let delay = backoff.next_delay();
+            info!("Reconnecting to Hermes in {:?}", delay);
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    async fn consume<S, E>(
+        &self,
+        mut stream: S,
+        sender: &UpdateSender,
+        last_event: &mut Option<Instant>,
+        backoff: &mut Backoff,
+    ) -> Disconnect
+    where
+        S: Stream<Item = Result<Event, E>> + Unpin,
+        E: fmt::Display,
+    {
+        loop {
+            let event =
+                match tokio::time::timeout(self.config.heartbeat_timeout, stream.next()).await {
+                    Err(_) => return Disconnect::Stalled(self.config.heartbeat_timeout),
+                    Ok(None) => return Disconnect::Closed,
+                    Ok(Some(Err(e))) => return Disconnect::Decode(e.to_string()),
+                    Ok(Some(Ok(event))) => event,
+                };
+
+            *last_event = Some(Instant::now());
+            backoff.reset();
+
+            if let Event::Message(msg) = event {
+                if let Some(Ok(line)) = msg.data().lines().next().await {
+                    if !forward(&line, sender).await {
+                        return Disconnect::Unsubscribed;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Replays the latest prices after a reconnect so no update window is lost
+    async fn backfill(&self, client: &Client, sender: &UpdateSender) -> bool {
+        let url = format!(
+            "{}/v2/updates/price/latest?{}&parsed=true",
+            self.config.hermes_url,
+            self.ids_query()
+        );
+        let latest = client
+            .get(&url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+
+        match latest {
+            Ok(response) => match response.text().await {
+                Ok(body) => return forward(&body, sender).await,
+                Err(e) => warn!("Failed to read backfill response: {}", e),
+            },
+            Err(e) => warn!("Failed to backfill latest prices: {}", e),
+        }
+        true
+    }
+}
+
+/// Parses a Hermes payload and pushes its updates, returning `false` once nobody is listening
+async fn forward(payload: &str, sender: &UpdateSender) -> bool {
+    let updates = match serde_json::from_str::<PythSSE>(payload) {
+        Ok(sse) => sse.into_updates().collect::<Vec<_>>(),
+        Err(e) => vec![Err(Errors::InvalidStreamMessage(e))],
+    };
+    for update in updates {
+        if sender.send(update).await.is_err() {
+            return false;
+        }
+    }
+    true
+}
+
+/// Fans a single price stream out to any number of subscribers
+pub struct PriceBroadcast {
+    sender: broadcast::Sender<PriceUpdate>,
+}
+
+impl PriceBroadcast {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        Self { sender }
+    }
+
+    /// Forwards another stream into the broadcast; aborting the handle drops the stream
+    pub fn attach(
+        &self,
+        updates: impl Stream<Item = Result<PriceUpdate, Errors>> + Send + 'static,
+    ) -> AbortHandle {
+        let tx = self.sender.clone();
+        tokio::spawn(async move {
+            let mut updates = std::pin::pin!(updates);
+            while let Some(update) = updates.next().await {
+                match update {
+                    // Having no subscribers right now is not an error
+                    Ok(update) => {
+                        let _ = tx.send(update);
+                    }
+                    // Malformed messages and disconnects are skipped, the stream carries on
+                    Err(e) => warn!("{}", e),
+                }
+            }
+        })
+        .abort_handle()
+    }
+
+    pub fn receiver(&self) -> broadcast::Receiver<PriceUpdate> {
+        self.sender.subscribe()
+    }
+
+    /// Subscribes as a stream; subscribers that fall behind skip the updates they missed
+    pub fn subscribe(&self) -> BoxStream<'static, PriceUpdate> {
+        stream::unfold(self.receiver(), |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(update) => return Some((update, receiver)),
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("Price subscriber lagged, skipped {} updates", skipped)
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        })
+        .boxed()
+    }
+}
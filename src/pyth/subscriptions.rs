@@ -0,0 +1,72 @@
+use super::{
+    stream::PriceBroadcast,
+    types::{FeedId, PythClient},
+};
+use std::collections::HashSet;
+use tokio::task::AbortHandle;
+use tracing::info;
+
+/// One Hermes connection per batch of feeds, all fanned into a single broadcast.
+///
+/// Changing the feed set only opens a connection for the added feeds, and only closes
+/// a connection once none of its feeds are wanted anymore.
+pub struct FeedSubscriptions {
+    client: PythClient,
+    broadcast: PriceBroadcast,
+    streams: Vec<(HashSet<FeedId>, AbortHandle)>,
+}
+
+impl FeedSubscriptions {
+    pub fn new(client: PythClient, capacity: usize) -> Self {
+        Self {
+            client,
+            broadcast: PriceBroadcast::new(capacity),
+            streams: Vec::new(),
+        }
+    }
+
+    pub fn broadcast(&self) -> &PriceBroadcast {
+        &self.broadcast
+    }
+
+    pub fn feeds(&self) -> HashSet<FeedId> {
+        self.streams
+            .iter()
+            .flat_map(|(feeds, _)| feeds.iter().copied())
+            .collect()
+    }
+
+    pub fn sync(&mut self, wanted: &[FeedId]) {
+        let wanted = wanted.iter().copied().collect::<HashSet<_>>();
+
+        self.streams.retain(|(feeds, handle)| {
+            let keep = !feeds.is_disjoint(&wanted);
+            if !keep {
+                info!("Closing price stream for {} unused feeds", feeds.len());
+                handle.abort();
+            }
+            keep
+        });
+
+        let added = wanted
+            .difference(&self.feeds())
+            .copied()
+            .collect::<HashSet<_>>();
+        if !added.is_empty() {
+            info!("Opening price stream for {} new feeds", added.len());
+            let stream = self
+                .client
+                .stream_price_feeds(added.iter().copied().collect());
+            let handle = self.broadcast.attach(stream);
+            self.streams.push((added, handle));
+        }
+    }
+}
+
+impl Drop for FeedSubscriptions {
+    fn drop(&mut self) {
+        for (_, handle) in self.streams.iter() {
+            handle.abort();
+        }
+    }
+}
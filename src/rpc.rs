@@ -0,0 +1,288 @@
+use crate::errors::Errors;
+use clap::ValueEnum;
+use futures::Future;
+use jsonrpsee::core::ClientError;
+use serde::{Deserialize, Serialize};
+use std::{
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use sui_sdk::{error::Error, SuiClient, SuiClientBuilder};
+use tokio::{sync::Mutex, time::Instant};
+use tracing::{info, warn};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Network {
+    #[default]
+    Mainnet,
+    Testnet,
+    Devnet,
+    /// A local `sui start` network
+    Localnet,
+}
+
+impl Network {
+    pub fn default_url(&self) -> &'static str {
+        match self {
+            Network::Mainnet => "https://fullnode.mainnet.sui.io:443",
+            Network::Testnet => "https://fullnode.testnet.sui.io:443",
+            Network::Devnet => "https://fullnode.devnet.sui.io:443",
+            Network::Localnet => "http://127.0.0.1:9000",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Strategy {
+    /// Spread requests over every healthy endpoint
+    #[default]
+    RoundRobin,
+    /// Always prefer the first healthy endpoint in config order
+    Failover,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EndpointConfig {
+    pub url: String,
+    /// Maximum requests per second sent to this endpoint
+    #[serde(default)]
+    pub rate_limit: Option<u32>,
+}
+
+/// RPC config; with no endpoints the network's public fullnode is used
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RpcConfig {
+    #[serde(default)]
+    pub network: Network,
+    #[serde(default)]
+    pub endpoints: Vec<EndpointConfig>,
+    #[serde(default)]
+    pub strategy: Strategy,
+    #[serde(default = "RpcConfig::default_health_check_secs")]
+    pub health_check_secs: u64,
+    #[serde(default = "RpcConfig::default_request_timeout_secs")]
+    pub request_timeout_secs: u64,
+}
+
+impl Default for RpcConfig {
+    fn default() -> Self {
+        Self {
+            network: Network::default(),
+            endpoints: Vec::new(),
+            strategy: Strategy::default(),
+            health_check_secs: Self::default_health_check_secs(),
+            request_timeout_secs: Self::default_request_timeout_secs(),
+        }
+    }
+}
+
+impl RpcConfig {
+    fn default_health_check_secs() -> u64 {
+        30
+    }
+
+    fn default_request_timeout_secs() -> u64 {
+        30
+    }
+
+    /// Reads a TOML, YAML or JSON file, picked by extension
+    pub fn from_file(path: &Path) -> Result<Self, Errors> {
+        crate::config::load_file(path)
+    }
+
+    pub fn endpoints(&self) -> Vec<EndpointConfig> {
+        if self.endpoints.is_empty() {
+            vec![EndpointConfig {
+                url: self.network.default_url().to_string(),
+                rate_limit: None,
+            }]
+        } else {
+            self.endpoints.clone()
+        }
+    }
+}
+
+/// Spaces requests evenly so an endpoint never sees more than `per_second`
+struct RateLimiter {
+    interval: Duration,
+    next_slot: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    fn new(per_second: u32) -> Self {
+        Self {
+            interval: Duration::from_secs(1) / per_second.max(1),
+            next_slot: Mutex::new(Instant::now()),
+        }
+    }
+
+    async fn acquire(&self) {
+        let slot = {
+            let mut next_slot = self.next_slot.lock().await;
+            let slot = (*next_slot).max(Instant::now());
+            *next_slot = slot + self.interval;
+            slot
+        };
+        tokio::time::sleep_until(slot).await;
+    }
+}
+
+struct Endpoint {
+    url: String,
+    client: SuiClient,
+    limiter: Option<RateLimiter>,
+    healthy: AtomicBool,
+}
+
+/// Long-lived Sui clients shared by everything that talks to the chain
+pub struct RpcPool {
+    endpoints: Vec<Endpoint>,
+    strategy: Strategy,
+    next: AtomicUsize,
+}
+
+impl RpcPool {
+    pub async fn connect(config: &RpcConfig) -> Result<Arc<Self>, Errors> {
+        let mut endpoints = Vec::new();
+        for endpoint in config.endpoints() {
+            let client = SuiClientBuilder::default()
+                .request_timeout(Duration::from_secs(config.request_timeout_secs))
+                .build(&endpoint.url)
+                .await;
+            match client {
+                Ok(client) => endpoints.push(Endpoint {
+                    url: endpoint.url,
+                    client,
+                    limiter: endpoint.rate_limit.map(RateLimiter::new),
+                    healthy: AtomicBool::new(true),
+                }),
+                Err(e) => warn!("Skipping RPC endpoint {}: {}", endpoint.url, e),
+            }
+        }
+        if endpoints.is_empty() {
+            return Err(Errors::NoHealthyRpcEndpoint);
+        }
+        info!(
+            "Connected to {} {:?} RPC endpoints",
+            endpoints.len(),
+            config.network
+        );
+
+        let pool = Arc::new(Self {
+            endpoints,
+            strategy: config.strategy,
+            next: AtomicUsize::new(0),
+        });
+        // Even a lone endpoint is marked down on transport errors, and needs restoring
+        tokio::spawn(
+            pool.clone()
+                .health_checks(Duration::from_secs(config.health_check_secs)),
+        );
+        Ok(pool)
+    }
+
+    /// Endpoint indices in the order they should be tried for the next request
+    fn candidates(&self) -> Vec<usize> {
+        let len = self.endpoints.len();
+        let start = match self.strategy {
+            Strategy::RoundRobin => self.next.fetch_add(1, Ordering::Relaxed) % len,
+            Strategy::Failover => 0,
+        };
+        let (healthy, unhealthy): (Vec<_>, Vec<_>) = (0..len)
+            .map(|offset| (start + offset) % len)
+            .partition(|i| self.endpoints[*i].healthy.load(Ordering::Relaxed));
+        // Unhealthy endpoints are still a last resort before giving up
+        healthy.into_iter().chain(unhealthy).collect()
+    }
+
+    /// Runs `request` against the pool, failing over to the next endpoint on transport errors
+    pub async fn call<T, F, Fut>(&self, request: F) -> Result<T, Errors>
+    where
+        F: Fn(SuiClient) -> Fut,
+        Fut: Future<Output = Result<T, Errors>>,
+    {
+        let mut last_error = Errors::NoHealthyRpcEndpoint;
+        for i in self.candidates() {
+            let endpoint = &self.endpoints[i];
+            if let Some(limiter) = endpoint.limiter.as_ref() {
+                limiter.acquire().await;
+            }
+            match request(endpoint.client.clone()).await {
+                // Errors the node answered with, e.g. a bad argument, would fail anywhere
+                Err(Errors::SuiError(e)) if is_transport_error(&e) => {
+                    warn!("RPC request to {} failed: {}", endpoint.url, e);
+                    endpoint.healthy.store(false, Ordering::Relaxed);
+                    last_error = Errors::SuiError(e);
+                }
+                result => return result,
+            }
+        }
+        Err(last_error)
+    }
+
+    async fn health_checks(self: Arc<Self>, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            for endpoint in self.endpoints.iter() {
+                let healthy = endpoint
+                    .client
+                    .read_api()
+                    .get_latest_checkpoint_sequence_number()
+                    .await
+                    .is_ok();
+                if healthy != endpoint.healthy.swap(healthy, Ordering::Relaxed) {
+                    info!(
+                        "RPC endpoint {} is now {}",
+                        endpoint.url,
+                        if healthy { "healthy" } else { "unhealthy" }
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// The endpoint could not be reached or did not answer, as opposed to answering with an error
+fn is_transport_error(error: &Error) -> bool {
+    matches!(
+        error,
+        Error::RpcError(
+            ClientError::Transport(_) | ClientError::RequestTimeout | ClientError::RestartNeeded(_)
+        )
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonrpsee::types::ErrorObject;
+
+    #[test]
+    fn fails_over_on_transport_errors() {
+        let unreachable = ClientError::Transport("connection refused".into());
+        assert!(is_transport_error(&Error::RpcError(unreachable)));
+        assert!(is_transport_error(&Error::RpcError(
+            ClientError::RequestTimeout
+        )));
+    }
+
+    #[test]
+    fn keeps_endpoints_that_answered() {
+        let invalid_params = ErrorObject::owned(-32602, "Invalid params", None::<()>);
+        assert!(!is_transport_error(&Error::RpcError(ClientError::Call(
+            invalid_params
+        ))));
+        assert!(!is_transport_error(&Error::DataError(
+            "object not found".to_string()
+        )));
+    }
+}
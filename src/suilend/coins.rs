@@ -0,0 +1,110 @@
+use crate::{
+    errors::Errors,
+    pyth::{registry, types::FeedId},
+    rpc::RpcPool,
+};
+use dashmap::DashMap;
+use std::{collections::BTreeMap, sync::LazyLock};
+
+/// Coin types whose struct name doesn't say which asset they are, by the feed's base asset
+const BUILTIN: &[(&str, &str)] = &[
+    ("0x2::sui::SUI", "SUI"),
+    (
+        "0x549e8b69270defbfafd4f94e17ec44cdbdd99820b33bda2278dea3b9a32d3f55::cert::CERT",
+        "VSUI",
+    ),
+    (
+        "0xf325ce1300e8dac124071d3152c5c5ee6174914f8bc2161e88329cf579246efc::afsui::AFSUI",
+        "AFSUI",
+    ),
+    (
+        "0xbde4ba4c2e274a60ce15c1cfff9e5c42e41654ac8b6d906a57efa4bd3c29f47d::hasui::HASUI",
+        "HASUI",
+    ),
+    // Wormhole bridged assets are all `coin::COIN`
+    (
+        "0x5d4b302506645c37ff133b98c4b50a5ae14841659738d6d733d59d0d217a93bf::coin::COIN",
+        "USDC",
+    ),
+    (
+        "0xc060006111016b8a020ad5b33834984a437aaa7d3c74c18e09a95d48aceab08c::coin::COIN",
+        "USDT",
+    ),
+    (
+        "0xaf8cd5edc19c4512f4259f0bee101a40d41ebed738ade5874359610ef8eeced5::coin::COIN",
+        "ETH",
+    ),
+    (
+        "0xdba34672e30cb065b1f93e3ab55318768fd6fef66c15942c9f7cb846e2f900e7::usdc::USDC",
+        "USDC",
+    ),
+];
+
+/// User supplied coin type → feed entries from `[suilend.coin_feeds]`
+static CONFIGURED: LazyLock<DashMap<String, FeedId>> = LazyLock::new(DashMap::new);
+
+/// Decimals of every reserve and reward coin seen so far
+static DECIMALS: LazyLock<DashMap<String, u8>> = LazyLock::new(DashMap::new);
+
+/// `0x2::sui::SUI` and Move's `TypeName` form `000…0002::sui::SUI` compare equal
+pub fn normalize(coin_type: &str) -> String {
+    match coin_type.split_once("::") {
+        Some((address, rest)) => {
+            let address = address.strip_prefix("0x").unwrap_or(address);
+            format!("{:0>64}::{}", address.to_lowercase(), rest)
+        }
+        None => coin_type.to_string(),
+    }
+}
+
+/// The coin's struct name, e.g. `SUI` for `0x2::sui::SUI`
+pub fn symbol(coin_type: &str) -> &str {
+    coin_type.rsplit("::").next().unwrap_or(coin_type)
+}
+
+pub fn install(coin_feeds: &BTreeMap<String, FeedId>) {
+    CONFIGURED.clear();
+    for (coin_type, feed) in coin_feeds.iter() {
+        CONFIGURED.insert(normalize(coin_type), *feed);
+    }
+}
+
+/// The feed for a coin whose reserve carries no usable price identifier
+pub fn fallback(coin_type: &str) -> Option<FeedId> {
+    let coin_type = normalize(coin_type);
+    if let Some(feed) = CONFIGURED.get(&coin_type) {
+        return Some(*feed);
+    }
+    BUILTIN
+        .iter()
+        .find(|(builtin, _)| normalize(builtin) == coin_type)
+        .and_then(|(_, base)| registry::by_base(base))
+}
+
+pub fn decimals(coin_type: &str) -> Option<u8> {
+    DECIMALS
+        .get(&normalize(coin_type))
+        .map(|decimals| *decimals)
+}
+
+pub fn set_decimals(coin_type: &str, decimals: u8) {
+    DECIMALS.insert(normalize(coin_type), decimals);
+}
+
+/// Decimals of a coin no reserve lists, e.g. a reward-only token, read from its `CoinMetadata`
+pub async fn load_decimals(rpc: &RpcPool, coin_type: &str) -> Result<u8, Errors> {
+    if let Some(decimals) = decimals(coin_type) {
+        return Ok(decimals);
+    }
+    let metadata = rpc
+        .call(|client| async move {
+            Ok(client
+                .coin_read_api()
+                .get_coin_metadata(coin_type.to_string())
+                .await?)
+        })
+        .await?
+        .ok_or_else(|| Errors::NoCoinMetadata(coin_type.to_string()))?;
+    set_decimals(coin_type, metadata.decimals);
+    Ok(metadata.decimals)
+}
@@ -0,0 +1,252 @@
+use crate::{
+    errors::Errors,
+    pyth::prices::{mul_div, PythPrice},
+};
+use move_core_types::u256::U256;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{fmt, str::FromStr};
+
+/// Move `suilend::decimal::Decimal`: an unsigned fixed-point number scaled by 10^18
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct SuilendDecimal(U256);
+
+impl SuilendDecimal {
+    pub fn zero() -> Self {
+        Self(U256::zero())
+    }
+
+    pub fn one() -> Self {
+        Self(wad_scale())
+    }
+
+    /// Wraps an already scaled WAD value
+    pub fn from_wad(value: U256) -> Self {
+        Self(value)
+    }
+
+    pub fn as_wad(&self) -> U256 {
+        self.0
+    }
+
+    pub fn from_integer(value: u64) -> Self {
+        Self(U256::from(value) * wad_scale())
+    }
+
+    pub fn from_bps(bps: u64) -> Self {
+        Self(U256::from(bps) * wad_scale() / U256::from(10_000u64))
+    }
+
+    pub fn from_percent(pct: u8) -> Self {
+        Self(U256::from(pct) * wad_scale() / U256::from(100u8))
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.0 == U256::zero()
+    }
+
+    pub fn checked_add(self, rhs: Self) -> Result<Self, Errors> {
+        self.0.checked_add(rhs.0).map(Self).ok_or(Errors::Overflow)
+    }
+
+    pub fn checked_sub(self, rhs: Self) -> Result<Self, Errors> {
+        self.0.checked_sub(rhs.0).map(Self).ok_or(Errors::Overflow)
+    }
+
+    pub fn saturating_sub(self, rhs: Self) -> Self {
+        self.checked_sub(rhs).unwrap_or_default()
+    }
+
+    pub fn checked_mul(self, rhs: Self) -> Result<Self, Errors> {
+        mul_div(self.0, rhs.0, wad_scale()).map(Self)
+    }
+
+    pub fn checked_div(self, rhs: Self) -> Result<Self, Errors> {
+        if rhs.is_zero() {
+            return Err(Errors::DivisionByZero);
+        }
+        mul_div(self.0, wad_scale(), rhs.0).map(Self)
+    }
+
+    /// `self^exp` by squaring, matching `suilend::decimal::pow`
+    pub fn checked_pow(self, mut exp: u64) -> Result<Self, Errors> {
+        let mut base = self;
+        let mut result = Self::one();
+        while exp > 0 {
+            if exp % 2 == 1 {
+                result = result.checked_mul(base)?;
+            }
+            exp /= 2;
+            if exp > 0 {
+                base = base.checked_mul(base)?;
+            }
+        }
+        Ok(result)
+    }
+
+    /// Integer part, e.g. a token amount in raw units
+    pub fn floor(&self) -> U256 {
+        self.0 / wad_scale()
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        self.0.to_string().parse::<f64>().unwrap_or(f64::MAX) / 1e18
+    }
+}
+
+fn wad_scale() -> U256 {
+    U256::from(1_000_000_000_000_000_000u64)
+}
+
+impl TryFrom<PythPrice> for SuilendDecimal {
+    type Error = Errors;
+
+    fn try_from(price: PythPrice) -> Result<Self, Self::Error> {
+        price.to_wad().map(Self)
+    }
+}
+
+impl FromStr for SuilendDecimal {
+    type Err = Errors;
+
+    /// Parses the raw WAD integer as stored on-chain
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        U256::from_str(s)
+            .map(Self)
+            .map_err(|_| Errors::MoveObjectParsingError("Decimal"))
+    }
+}
+
+/// Formats as a plain decimal, honouring `{:.N}` precision (truncating) and trimming
+/// trailing zeros otherwise
+impl fmt::Display for SuilendDecimal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let integer = self.0 / wad_scale();
+        let fraction = format!("{:0>18}", (self.0 % wad_scale()).to_string());
+        let fraction = match f.precision() {
+            Some(precision) => fraction[..precision.min(18)].to_string(),
+            None => fraction.trim_end_matches('0').to_string(),
+        };
+        if fraction.is_empty() {
+            write!(f, "{}", integer)
+        } else {
+            write!(f, "{}.{}", integer, fraction)
+        }
+    }
+}
+
+/// On-chain JSON renders a `Decimal` as `{ "value": "<wad>" }`
+#[derive(Serialize, Deserialize)]
+struct RawDecimal {
+    value: String,
+}
+
+impl Serialize for SuilendDecimal {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        RawDecimal {
+            value: self.0.to_string(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for SuilendDecimal {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = RawDecimal::deserialize(deserializer)?;
+        U256::from_str(&raw.value)
+            .map(Self)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decimal(wad: &str) -> SuilendDecimal {
+        wad.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_the_on_chain_wad_string() {
+        assert_eq!(
+            decimal("1500000000000000000"),
+            SuilendDecimal::from_bps(15_000)
+        );
+        assert_eq!(decimal("0"), SuilendDecimal::zero());
+        assert!("1.5".parse::<SuilendDecimal>().is_err());
+        assert!("-1".parse::<SuilendDecimal>().is_err());
+        assert!("".parse::<SuilendDecimal>().is_err());
+    }
+
+    #[test]
+    fn deserializes_the_json_form() {
+        let value: SuilendDecimal =
+            serde_json::from_str(r#"{ "value": "2500000000000000000" }"#).unwrap();
+        assert_eq!(value, SuilendDecimal::from_bps(25_000));
+        assert_eq!(
+            serde_json::to_string(&value).unwrap(),
+            r#"{"value":"2500000000000000000"}"#
+        );
+        assert!(serde_json::from_str::<SuilendDecimal>(r#"{ "value": "x" }"#).is_err());
+    }
+
+    #[test]
+    fn multiplies_and_divides_at_wad_precision() {
+        let two = SuilendDecimal::from_integer(2);
+        let three = SuilendDecimal::from_integer(3);
+        assert_eq!(
+            two.checked_mul(SuilendDecimal::from_bps(7_500)).unwrap(),
+            SuilendDecimal::from_bps(15_000)
+        );
+        // 2/3 truncates in the last place, like `suilend::decimal::div`
+        assert_eq!(
+            two.checked_div(three).unwrap(),
+            decimal("666666666666666666")
+        );
+        assert_eq!(
+            decimal("1").checked_mul(decimal("1")).unwrap(),
+            SuilendDecimal::zero()
+        );
+        assert_eq!(
+            SuilendDecimal::from_bps(10_500).checked_pow(2).unwrap(),
+            SuilendDecimal::from_bps(11_025)
+        );
+        assert_eq!(three.checked_pow(0).unwrap(), SuilendDecimal::one());
+    }
+
+    #[test]
+    fn reports_overflow_instead_of_panicking() {
+        let max = SuilendDecimal::from_wad(U256::max_value());
+        let one = SuilendDecimal::one();
+        assert!(matches!(max.checked_add(one), Err(Errors::Overflow)));
+        assert!(matches!(
+            SuilendDecimal::zero().checked_sub(one),
+            Err(Errors::Overflow)
+        ));
+        assert_eq!(
+            SuilendDecimal::zero().saturating_sub(one),
+            SuilendDecimal::zero()
+        );
+        assert!(matches!(max.checked_mul(max), Err(Errors::Overflow)));
+        assert!(matches!(
+            one.checked_div(SuilendDecimal::zero()),
+            Err(Errors::DivisionByZero)
+        ));
+        assert!(matches!(
+            SuilendDecimal::from_integer(10).checked_pow(100),
+            Err(Errors::Overflow)
+        ));
+    }
+
+    #[test]
+    fn displays_as_a_plain_decimal() {
+        assert_eq!(SuilendDecimal::from_integer(42).to_string(), "42");
+        assert_eq!(SuilendDecimal::zero().to_string(), "0");
+        assert_eq!(SuilendDecimal::from_bps(12_345).to_string(), "1.2345");
+        assert_eq!(decimal("1").to_string(), "0.000000000000000001");
+        // Precision truncates rather than rounds
+        assert_eq!(format!("{:.2}", decimal("1999000000000000000")), "1.99");
+        assert_eq!(format!("{:.3}", SuilendDecimal::from_integer(7)), "7.000");
+        assert_eq!(format!("{:.0}", SuilendDecimal::from_bps(15_000)), "1");
+    }
+}
@@ -0,0 +1,380 @@
+use super::{
+    decimal::SuilendDecimal,
+    health::TRACKED_OBLIGATIONS,
+    objects::CoinType,
+    reserves::{MarketConfig, Markets},
+};
+use crate::{errors::Errors, rpc::RpcPool};
+use move_core_types::identifier::Identifier;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+use sui_sdk::{
+    rpc_types::{EventFilter, EventPage, SuiEvent},
+    types::{
+        base_types::{ObjectID, SuiAddress},
+        event::EventID,
+    },
+};
+use tokio::{sync::mpsc, task::AbortHandle};
+use tracing::{info, warn};
+
+/// Modules whose events are ingested; `reserve` emits the interest refreshes
+const MODULES: [&str; 2] = ["lending_market", "reserve"];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EventsConfig {
+    pub enabled: bool,
+    pub poll_interval_ms: u64,
+    pub page_size: usize,
+    /// Where the last seen event of each module is kept between runs
+    pub cursor_file: PathBuf,
+}
+
+impl Default for EventsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            poll_interval_ms: 2000,
+            page_size: 50,
+            cursor_file: PathBuf::from("suilend_events.cursor.json"),
+        }
+    }
+}
+
+/// Deposit, withdraw, borrow, repay and reward claim events share this shape.
+///
+/// `amount` is in cTokens for deposits and withdrawals, underlying units otherwise.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ObligationEvent {
+    pub lending_market_id: ObjectID,
+    pub obligation_id: ObjectID,
+    pub coin_type: CoinType,
+    #[serde(alias = "ctoken_amount", alias = "liquidity_amount")]
+    pub amount: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LiquidateEvent {
+    pub lending_market_id: ObjectID,
+    pub obligation_id: ObjectID,
+    pub repay_coin_type: CoinType,
+    pub withdraw_coin_type: CoinType,
+    pub repay_amount: String,
+    /// cTokens seized from the obligation, including bonus and protocol fee
+    pub withdraw_amount: String,
+    pub protocol_fee_amount: String,
+    pub liquidator_bonus_amount: String,
+}
+
+/// `reserve::InterestUpdateEvent`, emitted whenever a reserve compounds its interest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReserveRefreshEvent {
+    pub lending_market_id: ObjectID,
+    pub coin_type: CoinType,
+    pub cumulative_borrow_rate: SuilendDecimal,
+    pub available_amount: String,
+    pub borrowed_amount: SuilendDecimal,
+    pub unclaimed_spread_fees: SuilendDecimal,
+    pub ctoken_supply: String,
+}
+
+#[derive(Debug, Clone)]
+pub enum EventKind {
+    Deposit(ObligationEvent),
+    Withdraw(ObligationEvent),
+    Borrow(ObligationEvent),
+    Repay(ObligationEvent),
+    ClaimReward(ObligationEvent),
+    Liquidate(LiquidateEvent),
+    ReserveRefresh(ReserveRefreshEvent),
+}
+
+impl EventKind {
+    fn decode(name: &str, json: serde_json::Value) -> Result<Option<Self>, Errors> {
+        Ok(Some(match name {
+            "DepositEvent" => EventKind::Deposit(serde_json::from_value(json)?),
+            "WithdrawEvent" => EventKind::Withdraw(serde_json::from_value(json)?),
+            "BorrowEvent" => EventKind::Borrow(serde_json::from_value(json)?),
+            "RepayEvent" => EventKind::Repay(serde_json::from_value(json)?),
+            "ClaimRewardEvent" => EventKind::ClaimReward(serde_json::from_value(json)?),
+            "LiquidateEvent" => EventKind::Liquidate(serde_json::from_value(json)?),
+            "InterestUpdateEvent" => EventKind::ReserveRefresh(serde_json::from_value(json)?),
+            _ => return Ok(None),
+        }))
+    }
+
+    pub fn obligation_id(&self) -> Option<ObjectID> {
+        match self {
+            EventKind::Deposit(e)
+            | EventKind::Withdraw(e)
+            | EventKind::Borrow(e)
+            | EventKind::Repay(e)
+            | EventKind::ClaimReward(e) => Some(e.obligation_id),
+            EventKind::Liquidate(e) => Some(e.obligation_id),
+            EventKind::ReserveRefresh(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for EventKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EventKind::Deposit(e) => write!(
+                f,
+                "Deposit of {} {} cTokens",
+                e.amount,
+                e.coin_type.symbol()
+            ),
+            EventKind::Withdraw(e) => write!(
+                f,
+                "Withdrawal of {} {} cTokens",
+                e.amount,
+                e.coin_type.symbol()
+            ),
+            EventKind::Borrow(e) => write!(f, "Borrow of {} {}", e.amount, e.coin_type.symbol()),
+            EventKind::Repay(e) => write!(f, "Repayment of {} {}", e.amount, e.coin_type.symbol()),
+            EventKind::ClaimReward(e) => {
+                write!(
+                    f,
+                    "Claimed {} {} in rewards",
+                    e.amount,
+                    e.coin_type.symbol()
+                )
+            }
+            EventKind::Liquidate(e) => write!(
+                f,
+                "Liquidated: {} {} repaid, {} {} cTokens seized",
+                e.repay_amount,
+                e.repay_coin_type.symbol(),
+                e.withdraw_amount,
+                e.withdraw_coin_type.symbol()
+            ),
+            EventKind::ReserveRefresh(e) => write!(f, "{} reserve refreshed", e.coin_type.symbol()),
+        }
+    }
+}
+
+/// A decoded Suilend event from the configured lending market
+#[derive(Debug, Clone)]
+pub struct SuilendEvent {
+    pub id: EventID,
+    /// Transaction sender, the liquidator for liquidations
+    pub sender: SuiAddress,
+    pub timestamp_ms: Option<u64>,
+    pub kind: EventKind,
+}
+
+impl SuilendEvent {
+    fn decode(event: SuiEvent) -> Result<Option<Self>, Errors> {
+        let name = event.type_.name.to_string();
+        let kind =
+            EventKind::decode(&name, event.parsed_json).map_err(|source| Errors::InvalidEvent {
+                event: name.clone(),
+                tx_digest: event.id.tx_digest.to_string(),
+                source: Box::new(source),
+            })?;
+        let Some(kind) = kind else {
+            return Ok(None);
+        };
+        Ok(Some(Self {
+            id: event.id,
+            sender: event.sender,
+            timestamp_ms: event.timestamp_ms,
+            kind,
+        }))
+    }
+}
+
+/// Last event seen per module, persisted so a restart resumes where it stopped
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Cursors(BTreeMap<String, EventID>);
+
+impl Cursors {
+    fn load(path: &Path) -> Self {
+        match std::fs::read_to_string(path) {
+            Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+                warn!("Ignoring unreadable event cursor {}: {}", path.display(), e);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    fn save(&self, path: &Path) -> Result<(), Errors> {
+        // Written aside and renamed so a crash never leaves a torn file
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, serde_json::to_vec(self)?)?;
+        std::fs::rename(tmp, path)?;
+        Ok(())
+    }
+}
+
+/// Tails Suilend events touching watched obligations.
+///
+/// Sui fullnodes no longer offer event subscriptions, so this pages through
+/// `query_events` from a cursor instead.
+pub struct EventIngestor {
+    receiver: mpsc::Receiver<SuilendEvent>,
+    task: AbortHandle,
+}
+
+impl EventIngestor {
+    pub fn spawn(
+        rpc: Arc<RpcPool>,
+        markets: Markets,
+        market: &MarketConfig,
+        config: &EventsConfig,
+    ) -> Result<Self, Errors> {
+        let package = market.package_id()?;
+        let tailer = Tailer {
+            rpc,
+            markets,
+            filters: MODULES
+                .iter()
+                .map(|module| {
+                    let filter = EventFilter::MoveEventModule {
+                        package,
+                        module: Identifier::new(*module).expect("valid Move identifier"),
+                    };
+                    (module.to_string(), filter)
+                })
+                .collect(),
+            cursor_file: config.cursor_file.clone(),
+            page_size: config.page_size,
+            poll_interval: Duration::from_millis(config.poll_interval_ms),
+        };
+        let (sender, receiver) = mpsc::channel(256);
+        let task = tokio::spawn(tailer.run(sender)).abort_handle();
+        Ok(Self { receiver, task })
+    }
+
+    pub async fn recv(&mut self) -> Option<SuilendEvent> {
+        self.receiver.recv().await
+    }
+}
+
+impl Drop for EventIngestor {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+struct Tailer {
+    rpc: Arc<RpcPool>,
+    markets: Markets,
+    filters: Vec<(String, EventFilter)>,
+    cursor_file: PathBuf,
+    page_size: usize,
+    poll_interval: Duration,
+}
+
+impl Tailer {
+    async fn run(self, sender: mpsc::Sender<SuilendEvent>) {
+        let mut cursors = Cursors::load(&self.cursor_file);
+        loop {
+            for (module, filter) in self.filters.iter() {
+                let cursor = match cursors.0.get(module) {
+                    Some(cursor) => Some(*cursor),
+                    // Nothing to resume from: start at the newest event, not genesis
+                    None => match self.latest(filter).await {
+                        Ok(cursor) => cursor,
+                        Err(e) => {
+                            warn!("Cannot find the latest {} event: {}", module, e);
+                            continue;
+                        }
+                    },
+                };
+                let Some(mut cursor) = cursor else {
+                    continue;
+                };
+
+                loop {
+                    let page = match self.page(filter, cursor).await {
+                        Ok(page) => page,
+                        Err(e) => {
+                            warn!("Failed to query {} events: {}", module, e);
+                            break;
+                        }
+                    };
+                    for event in page.data {
+                        match SuilendEvent::decode(event) {
+                            Ok(Some(event)) if self.wanted(&event) => {
+                                if sender.send(event).await.is_err() {
+                                    return;
+                                }
+                            }
+                            Ok(_) => {}
+                            Err(e) => warn!("Skipping undecodable {} event: {}", module, e),
+                        }
+                    }
+                    if let Some(next) = page.next_cursor {
+                        cursor = next;
+                    }
+                    if !page.has_next_page {
+                        break;
+                    }
+                }
+
+                cursors.0.insert(module.clone(), cursor);
+                if let Err(e) = cursors.save(&self.cursor_file) {
+                    warn!("Failed to persist event cursor: {}", e);
+                }
+            }
+            tokio::time::sleep(self.poll_interval).await;
+        }
+    }
+
+    /// Events of tracked obligations, and refreshes of the reserves in the markets they live in
+    fn wanted(&self, event: &SuilendEvent) -> bool {
+        match &event.kind {
+            EventKind::ReserveRefresh(refresh) => self
+                .markets
+                .get(&refresh.lending_market_id)
+                .is_ok_and(|reserves| reserves.by_coin(&refresh.coin_type.name).is_ok()),
+            kind => kind
+                .obligation_id()
+                .is_some_and(|id| TRACKED_OBLIGATIONS.contains_key(&id)),
+        }
+    }
+
+    async fn latest(&self, filter: &EventFilter) -> Result<Option<EventID>, Errors> {
+        let newest = self
+            .rpc
+            .call(|client| {
+                let filter = filter.clone();
+                async move {
+                    Ok(client
+                        .event_api()
+                        .query_events(filter, None, Some(1), true)
+                        .await?)
+                }
+            })
+            .await?;
+        let cursor = newest.data.first().map(|event| event.id);
+        if cursor.is_some() {
+            info!("Following Suilend events from the latest one");
+        }
+        Ok(cursor)
+    }
+
+    async fn page(&self, filter: &EventFilter, cursor: EventID) -> Result<EventPage, Errors> {
+        self.rpc
+            .call(|client| {
+                let filter = filter.clone();
+                async move {
+                    Ok(client
+                        .event_api()
+                        .query_events(filter, Some(cursor), Some(self.page_size), false)
+                        .await?)
+                }
+            })
+            .await
+    }
+}
@@ -0,0 +1,475 @@
+use super::{
+    decimal::SuilendDecimal,
+    interest::{time_to_liquidation, unix_now, BorrowAccrual},
+    objects::Obligation,
+    reserves::{ReserveInfo, ReserveRegistry},
+    types::{BorrowLimit, Bw, CloseLtv, LiquidationThreshold, OpenLtv, WeightedBorrow},
+};
+use crate::{
+    errors::Errors,
+    pyth::{
+        prices::{PythPrice, LATEST_EMA_PRICES, LATEST_PRICES},
+        registry,
+        types::FeedId,
+    },
+};
+use dashmap::DashMap;
+use std::{fmt, sync::LazyLock, time::Duration};
+use sui_sdk::types::base_types::ObjectID;
+use tracing::warn;
+
+/// Obligations being watched, re-evaluated whenever one of their feeds ticks
+pub static TRACKED_OBLIGATIONS: LazyLock<DashMap<ObjectID, TrackedObligation>> =
+    LazyLock::new(DashMap::new);
+
+/// A single deposit or borrow of `amount` whole underlying tokens.
+///
+/// Valued at the live Pyth price, falling back to the on-chain `market_value`
+/// until the feed has ticked.
+#[derive(Debug)]
+pub struct Leg {
+    pub reserve_array_index: u64,
+    pub feed: FeedId,
+    pub amount: SuilendDecimal,
+    pub market_value: SuilendDecimal,
+}
+
+impl Leg {
+    fn value_at(&self, price: PythPrice) -> Result<SuilendDecimal, Errors> {
+        self.amount.checked_mul(SuilendDecimal::try_from(price)?)
+    }
+
+    pub fn current_value(&self) -> Result<SuilendDecimal, Errors> {
+        match LATEST_PRICES.get(&self.feed).map(|p| *p) {
+            Some(price) => self.value_at(price),
+            None => Ok(self.market_value),
+        }
+    }
+
+    /// Valued at Min(EMA, Latest Price), as Suilend does for the borrow limit
+    pub fn lower_bound_value(&self) -> Result<SuilendDecimal, Errors> {
+        let current = self.current_value()?;
+        match LATEST_EMA_PRICES.get(&self.feed).map(|p| *p) {
+            Some(ema) => Ok(current.min(self.value_at(ema)?)),
+            None => Ok(current),
+        }
+    }
+}
+
+impl fmt::Display for Leg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {:.6} (${:.2})",
+            registry::name(&self.feed),
+            self.amount,
+            self.current_value().unwrap_or(self.market_value)
+        )
+    }
+}
+
+#[derive(Debug)]
+pub struct CollateralLeg {
+    pub leg: Leg,
+    pub open_ltv: OpenLtv,
+    pub close_ltv: CloseLtv,
+}
+
+#[derive(Debug)]
+pub struct DebtLeg {
+    pub leg: Leg,
+    pub borrow_weight: Bw,
+    pub accrual: BorrowAccrual,
+}
+
+#[derive(Debug)]
+pub struct TrackedObligation {
+    pub id: ObjectID,
+    /// Watch list entry this obligation was found through
+    pub label: String,
+    pub deposits: Vec<CollateralLeg>,
+    pub borrows: Vec<DebtLeg>,
+    /// The read this was built from, to tell what changed on the next one
+    pub obligation: Obligation,
+}
+
+impl TrackedObligation {
+    pub fn new(
+        label: String,
+        obligation: &Obligation,
+        reserves: &ReserveRegistry,
+    ) -> Result<Self, Errors> {
+        Ok(Self {
+            id: obligation.id.id,
+            label,
+            deposits: obligation
+                .deposits
+                .iter()
+                .map(|d| {
+                    let reserve = reserves.by_index(&d.reserve_array_index)?;
+                    Ok(CollateralLeg {
+                        leg: Leg {
+                            reserve_array_index: reserve.array_index,
+                            feed: reserve.feed()?,
+                            amount: reserve.to_tokens(
+                                reserve.ctokens_to_underlying(d.ctokens(obligation.id.id)?)?,
+                            )?,
+                            market_value: d.market_value,
+                        },
+                        open_ltv: reserve.open_ltv,
+                        close_ltv: reserve.close_ltv,
+                    })
+                })
+                .collect::<Result<_, Errors>>()?,
+            borrows: obligation
+                .borrows
+                .iter()
+                .map(|b| {
+                    let reserve = reserves.by_index(&b.reserve_array_index)?;
+                    Ok(DebtLeg {
+                        leg: Leg {
+                            reserve_array_index: reserve.array_index,
+                            feed: reserve.feed()?,
+                            amount: reserve.to_tokens(b.borrowed_amount)?,
+                            market_value: b.market_value,
+                        },
+                        borrow_weight: reserve.borrow_weight,
+                        accrual: reserve.accrual(b.cumulative_borrow_rate)?,
+                    })
+                })
+                .collect::<Result<_, Errors>>()?,
+            obligation: obligation.clone(),
+        })
+    }
+
+    /// Re-projects the borrows of `reserve` after it compounded its interest on chain
+    pub fn on_reserve_refresh(&mut self, reserve: &ReserveInfo) -> Result<(), Errors> {
+        for borrow in self
+            .borrows
+            .iter_mut()
+            .filter(|b| b.leg.reserve_array_index == reserve.array_index)
+        {
+            borrow.accrual = reserve.accrual(borrow.accrual.borrow_rate)?;
+        }
+        Ok(())
+    }
+
+    pub fn deposit_leg(&self, reserve_array_index: u64) -> Option<&Leg> {
+        self.deposits
+            .iter()
+            .map(|d| &d.leg)
+            .find(|leg| leg.reserve_array_index == reserve_array_index)
+    }
+
+    pub fn borrow_leg(&self, reserve_array_index: u64) -> Option<&Leg> {
+        self.borrows
+            .iter()
+            .map(|b| &b.leg)
+            .find(|leg| leg.reserve_array_index == reserve_array_index)
+    }
+
+    pub fn uses_feed(&self, feed: &FeedId) -> bool {
+        self.deposits
+            .iter()
+            .map(|d| &d.leg)
+            .chain(self.borrows.iter().map(|b| &b.leg))
+            .any(|leg| &leg.feed == feed)
+    }
+
+    pub fn evaluate(&self) -> Result<ObligationHealth, Errors> {
+        let now = unix_now();
+        let deposits = self
+            .deposits
+            .iter()
+            .map(|d| {
+                Ok((
+                    d.leg.current_value()?,
+                    d.leg.lower_bound_value()?,
+                    d.open_ltv,
+                    d.close_ltv,
+                ))
+            })
+            .collect::<Result<Vec<_>, Errors>>()?;
+        let borrows = self
+            .borrows
+            .iter()
+            .map(|b| {
+                let value = b
+                    .leg
+                    .current_value()?
+                    .checked_mul(b.accrual.growth_at(now)?)?;
+                Ok((value, b.borrow_weight, b.accrual.apr))
+            })
+            .collect::<Result<Vec<_>, Errors>>()?;
+
+        let mut deposited_value_usd = SuilendDecimal::zero();
+        let mut borrow_limit = SuilendDecimal::zero();
+        let mut liquidation_threshold = SuilendDecimal::zero();
+        for (value_usd, lower_bound_usd, open_ltv, close_ltv) in deposits {
+            deposited_value_usd = deposited_value_usd.checked_add(value_usd)?;
+            borrow_limit = borrow_limit.checked_add(
+                BorrowLimit {
+                    value_usd: lower_bound_usd,
+                    open_ltv,
+                }
+                .get_limit()?,
+            )?;
+            liquidation_threshold = liquidation_threshold.checked_add(
+                LiquidationThreshold {
+                    value_usd,
+                    close_ltv,
+                }
+                .get_threshold()?,
+            )?;
+        }
+
+        let weighted_borrows = borrows
+            .into_iter()
+            .map(|(value_usd, borrow_weight, apr)| {
+                let weighted = WeightedBorrow {
+                    value_usd,
+                    borrow_weight,
+                }
+                .calculate_weighted_borrow()?;
+                Ok((weighted, apr))
+            })
+            .collect::<Result<Vec<_>, Errors>>()?;
+        let weighted_borrow = weighted_borrows
+            .iter()
+            .try_fold(SuilendDecimal::zero(), |total, (w, _)| {
+                total.checked_add(*w)
+            })?;
+
+        let mut health = ObligationHealth::new(
+            self.id,
+            self.label.clone(),
+            deposited_value_usd,
+            weighted_borrow,
+            borrow_limit,
+            liquidation_threshold,
+        );
+        if !weighted_borrow.is_zero() {
+            let debts = weighted_borrows
+                .iter()
+                .map(|(w, apr)| (w.to_f64(), apr.to_f64()))
+                .collect::<Vec<_>>();
+            health.interest_liquidation_eta =
+                time_to_liquidation(&debts, liquidation_threshold.to_f64());
+        }
+        Ok(health)
+    }
+}
+
+impl fmt::Display for TrackedObligation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{}] Obligation: {}", self.label, self.id)?;
+        for deposit in self.deposits.iter() {
+            write!(f, "\n  Deposit {}", deposit.leg)?;
+        }
+        for borrow in self.borrows.iter() {
+            write!(f, "\n  Borrow {}", borrow.leg)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ObligationHealth {
+    pub obligation_id: ObjectID,
+    pub label: String,
+    pub deposited_value_usd: SuilendDecimal,
+    pub weighted_borrow: SuilendDecimal,
+    pub borrow_limit: SuilendDecimal,
+    pub liquidation_threshold: SuilendDecimal,
+    /// liquidation_threshold / weighted_borrow, `None` when nothing is borrowed
+    pub health_ratio: Option<f64>,
+    /// Uniform collateral price drop (in %) that would make the obligation liquidatable
+    pub liquidation_drop_pct: Option<f64>,
+    /// Time until accrued interest alone makes the obligation liquidatable, at current prices
+    pub interest_liquidation_eta: Option<Duration>,
+}
+
+impl ObligationHealth {
+    pub fn new(
+        obligation_id: ObjectID,
+        label: String,
+        deposited_value_usd: SuilendDecimal,
+        weighted_borrow: SuilendDecimal,
+        borrow_limit: SuilendDecimal,
+        liquidation_threshold: SuilendDecimal,
+    ) -> Self {
+        let (health_ratio, liquidation_drop_pct) = match (
+            liquidation_threshold.checked_div(weighted_borrow),
+            weighted_borrow.checked_div(liquidation_threshold),
+        ) {
+            (Ok(health), Ok(utilization)) => (
+                Some(health.to_f64()),
+                Some((1.0 - utilization.to_f64()).max(0.0) * 100.0),
+            ),
+            (Ok(health), Err(_)) => (Some(health.to_f64()), Some(0.0)),
+            _ => (None, None),
+        };
+
+        Self {
+            obligation_id,
+            label,
+            deposited_value_usd,
+            weighted_borrow,
+            borrow_limit,
+            liquidation_threshold,
+            health_ratio,
+            liquidation_drop_pct,
+            interest_liquidation_eta: None,
+        }
+    }
+}
+
+impl fmt::Display for ObligationHealth {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "[{}] Obligation: {}", self.label, self.obligation_id)?;
+        writeln!(f, "Deposit Value: {:.2}", self.deposited_value_usd)?;
+        writeln!(f, "Weighted Borrow: {:.2}", self.weighted_borrow)?;
+        writeln!(f, "Borrow Limit: {:.2}", self.borrow_limit)?;
+        writeln!(f, "Liq Threshold: {:.2}", self.liquidation_threshold)?;
+        match (self.health_ratio, self.liquidation_drop_pct) {
+            (Some(health), Some(drop)) => {
+                writeln!(f, "Health: {:.4}", health)?;
+                write!(f, "Liq % Away: {:.2}", drop)?;
+                match self.interest_liquidation_eta {
+                    Some(eta) => write!(
+                        f,
+                        "\nLiq From Interest In: {:.1} days",
+                        eta.as_secs_f64() / 86_400.0
+                    ),
+                    None => Ok(()),
+                }
+            }
+            _ => write!(f, "Health: no borrows"),
+        }
+    }
+}
+
+/// Re-evaluates every tracked obligation exposed to `feed`
+pub fn evaluate_feed(feed: &FeedId) -> Vec<ObligationHealth> {
+    TRACKED_OBLIGATIONS
+        .iter()
+        .filter(|e| e.uses_feed(feed))
+        .filter_map(|e| match e.evaluate() {
+            Ok(health) => Some(health),
+            Err(err) => {
+                warn!("Skipping health of {}: {}", e.id, err);
+                None
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::suilend::testing::{borrow, deposit, obligation, registry, SUI, USDC};
+
+    /// Feeds are unique per test, as the price maps are shared by the whole test binary
+    fn feeds(seed: u8) -> (FeedId, FeedId) {
+        (
+            FeedId::from_bytes([seed; 32]),
+            FeedId::from_bytes([seed + 1; 32]),
+        )
+    }
+
+    fn usd(cents: i64) -> PythPrice {
+        PythPrice::new(cents, 0, -2, 0)
+    }
+
+    fn dollars(value: u64) -> SuilendDecimal {
+        SuilendDecimal::from_integer(value)
+    }
+
+    /// 10 SUI deposited against `usdc` USDC borrowed
+    fn tracked(sui: FeedId, usdc: FeedId, borrowed: u64) -> TrackedObligation {
+        let borrows = match borrowed {
+            0 => vec![],
+            usdc => vec![borrow(7, USDC, usdc * 1_000_000, SuilendDecimal::one())],
+        };
+        let obligation = obligation(vec![deposit(0, SUI, 10_000_000_000)], borrows);
+        TrackedObligation::new("test".to_string(), &obligation, &registry(sui, usdc)).unwrap()
+    }
+
+    #[test]
+    fn values_the_borrow_limit_at_the_lower_of_ema_and_spot() {
+        let (sui, usdc) = feeds(0x10);
+        LATEST_PRICES.insert(sui, usd(200));
+        LATEST_EMA_PRICES.insert(sui, usd(150));
+        LATEST_PRICES.insert(usdc, usd(100));
+
+        let health = tracked(sui, usdc, 8).evaluate().unwrap();
+        assert_eq!(health.deposited_value_usd, dollars(20));
+        // 70% of 10 SUI at the $1.50 EMA, while the threshold takes 80% at the $2 spot
+        assert_eq!(health.borrow_limit, SuilendDecimal::from_bps(105_000));
+        assert_eq!(health.liquidation_threshold, dollars(16));
+        assert_eq!(health.weighted_borrow, dollars(8));
+        assert_eq!(health.health_ratio, Some(2.0));
+        assert_eq!(health.liquidation_drop_pct, Some(50.0));
+
+        // An EMA above spot leaves the spot price in charge
+        LATEST_EMA_PRICES.insert(sui, usd(300));
+        let health = tracked(sui, usdc, 8).evaluate().unwrap();
+        assert_eq!(health.borrow_limit, dollars(14));
+    }
+
+    #[test]
+    fn has_no_ratio_without_borrows() {
+        let (sui, usdc) = feeds(0x20);
+        LATEST_PRICES.insert(sui, usd(200));
+
+        let health = tracked(sui, usdc, 0).evaluate().unwrap();
+        assert_eq!(health.weighted_borrow, SuilendDecimal::zero());
+        assert_eq!(health.liquidation_threshold, dollars(16));
+        assert_eq!(health.health_ratio, None);
+        assert_eq!(health.liquidation_drop_pct, None);
+        assert_eq!(health.interest_liquidation_eta, None);
+        assert_eq!(
+            health.to_string().lines().last(),
+            Some("Health: no borrows")
+        );
+    }
+
+    #[test]
+    fn is_liquidatable_once_the_borrow_passes_the_threshold() {
+        let (sui, usdc) = feeds(0x30);
+        LATEST_PRICES.insert(sui, usd(100));
+        LATEST_PRICES.insert(usdc, usd(100));
+
+        let health = tracked(sui, usdc, 10).evaluate().unwrap();
+        assert_eq!(health.liquidation_threshold, dollars(8));
+        assert_eq!(health.health_ratio, Some(0.8));
+        // Already past it, so no drop is needed and interest has nothing left to do
+        assert_eq!(health.liquidation_drop_pct, Some(0.0));
+        assert_eq!(health.interest_liquidation_eta, Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn falls_back_to_the_on_chain_value_before_a_price() {
+        let (sui, usdc) = feeds(0x40);
+        let mut tracked = tracked(sui, usdc, 0);
+        tracked.deposits[0].leg.market_value = dollars(25);
+
+        let health = tracked.evaluate().unwrap();
+        assert_eq!(health.deposited_value_usd, dollars(25));
+        assert_eq!(health.liquidation_threshold, dollars(20));
+    }
+
+    #[test]
+    fn treats_a_zero_threshold_as_no_room_left() {
+        let health = ObligationHealth::new(
+            ObjectID::ZERO,
+            "test".to_string(),
+            SuilendDecimal::zero(),
+            dollars(5),
+            SuilendDecimal::zero(),
+            SuilendDecimal::zero(),
+        );
+        assert_eq!(health.health_ratio, Some(0.0));
+        assert_eq!(health.liquidation_drop_pct, Some(0.0));
+    }
+}
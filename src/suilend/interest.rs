@@ -0,0 +1,235 @@
+use super::decimal::SuilendDecimal;
+use crate::errors::Errors;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub const SECONDS_PER_YEAR: u64 = 365 * 24 * 60 * 60;
+
+/// Longest horizon searched when forecasting liquidation from interest alone
+const MAX_FORECAST: Duration = Duration::from_secs(100 * SECONDS_PER_YEAR);
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Yield of `apr` compounded every second, as Suilend accrues interest
+pub fn apr_to_apy(apr: f64) -> f64 {
+    let periods = SECONDS_PER_YEAR as f64;
+    ((apr / periods).ln_1p() * periods).exp_m1()
+}
+
+/// Piecewise linear borrow APR curve of a reserve.
+///
+/// `utils` are utilization breakpoints in percent, `aprs` the matching APRs in basis points.
+#[derive(Debug, Clone, Default)]
+pub struct InterestRateCurve {
+    pub utils: Vec<u8>,
+    pub aprs: Vec<u64>,
+}
+
+impl InterestRateCurve {
+    /// Borrow APR at `utilization` (a fraction), interpolated like `reserve_config::calculate_apr`
+    pub fn apr(&self, utilization: SuilendDecimal) -> Result<SuilendDecimal, Errors> {
+        let points = self
+            .utils
+            .iter()
+            .zip(self.aprs.iter())
+            .map(|(util, apr)| {
+                (
+                    SuilendDecimal::from_percent(*util),
+                    SuilendDecimal::from_bps(*apr),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let Some(&(_, last_apr)) = points.last() else {
+            return Ok(SuilendDecimal::zero());
+        };
+        for window in points.windows(2) {
+            let ((left_util, left_apr), (right_util, right_apr)) = (window[0], window[1]);
+            if utilization < left_util || utilization > right_util {
+                continue;
+            }
+            let span = right_util.checked_sub(left_util)?;
+            if span.is_zero() {
+                return Ok(left_apr);
+            }
+            let weight = utilization.checked_sub(left_util)?.checked_div(span)?;
+            // APRs are non-decreasing along the curve but interpolate both ways to be safe
+            return if right_apr >= left_apr {
+                left_apr.checked_add(right_apr.checked_sub(left_apr)?.checked_mul(weight)?)
+            } else {
+                left_apr.checked_sub(left_apr.checked_sub(right_apr)?.checked_mul(weight)?)
+            };
+        }
+        Ok(last_apr)
+    }
+}
+
+/// Everything needed to project a borrow's debt forward between on-chain refreshes
+#[derive(Debug, Clone)]
+pub struct BorrowAccrual {
+    /// `cumulative_borrow_rate` stored on the obligation's `Borrow`
+    pub borrow_rate: SuilendDecimal,
+    /// Reserve `cumulative_borrow_rate` as of `reserve_updated_s`
+    pub reserve_rate: SuilendDecimal,
+    pub reserve_updated_s: u64,
+    pub apr: SuilendDecimal,
+}
+
+impl BorrowAccrual {
+    /// Reserve `cumulative_borrow_rate` compounded per second up to `now_s`,
+    /// as `reserve::compound_interest` would
+    pub fn reserve_rate_at(&self, now_s: u64) -> Result<SuilendDecimal, Errors> {
+        let elapsed = now_s.saturating_sub(self.reserve_updated_s);
+        let per_second = self
+            .apr
+            .checked_div(SuilendDecimal::from_integer(SECONDS_PER_YEAR))?;
+        self.reserve_rate.checked_mul(
+            SuilendDecimal::one()
+                .checked_add(per_second)?
+                .checked_pow(elapsed)?,
+        )
+    }
+
+    /// Factor by which the debt has grown since the obligation was last refreshed
+    pub fn growth_at(&self, now_s: u64) -> Result<SuilendDecimal, Errors> {
+        if self.borrow_rate.is_zero() {
+            return Ok(SuilendDecimal::one());
+        }
+        self.reserve_rate_at(now_s)?.checked_div(self.borrow_rate)
+    }
+}
+
+/// Time until compounding debt alone pushes `debts` past `threshold`, assuming prices hold.
+///
+/// `debts` are `(weighted borrow in USD, APR)` pairs. Returns `None` when the debt never
+/// catches up within the forecast horizon.
+pub fn time_to_liquidation(debts: &[(f64, f64)], threshold: f64) -> Option<Duration> {
+    let debt_at = |t: f64| -> f64 {
+        debts
+            .iter()
+            .map(|(value, apr)| value * (apr * t / SECONDS_PER_YEAR as f64).exp())
+            .sum()
+    };
+
+    if debt_at(0.0) >= threshold {
+        return Some(Duration::ZERO);
+    }
+    let (mut low, mut high) = (0.0, MAX_FORECAST.as_secs_f64());
+    if debt_at(high) < threshold {
+        return None;
+    }
+    while high - low > 60.0 {
+        let mid = (low + high) / 2.0;
+        if debt_at(mid) >= threshold {
+            high = mid;
+        } else {
+            low = mid;
+        }
+    }
+    Some(Duration::from_secs_f64(high))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A kinked curve in `reserve_config` form: 0% → 2%, 80% → 10%, 100% → 150% APR
+    fn curve() -> InterestRateCurve {
+        InterestRateCurve {
+            utils: vec![0, 80, 100],
+            aprs: vec![200, 1_000, 15_000],
+        }
+    }
+
+    fn pct(pct: u8) -> SuilendDecimal {
+        SuilendDecimal::from_percent(pct)
+    }
+
+    #[test]
+    fn reads_breakpoints_exactly() {
+        let curve = curve();
+        assert_eq!(curve.apr(pct(0)).unwrap(), SuilendDecimal::from_bps(200));
+        assert_eq!(curve.apr(pct(80)).unwrap(), SuilendDecimal::from_bps(1_000));
+        assert_eq!(
+            curve.apr(pct(100)).unwrap(),
+            SuilendDecimal::from_bps(15_000)
+        );
+    }
+
+    #[test]
+    fn interpolates_between_breakpoints() {
+        let curve = curve();
+        // Halfway up the first segment: 2% + (10% - 2%) / 2
+        assert_eq!(curve.apr(pct(40)).unwrap(), SuilendDecimal::from_bps(600));
+        // Halfway up the kink: 10% + (150% - 10%) / 2
+        assert_eq!(curve.apr(pct(90)).unwrap(), SuilendDecimal::from_bps(8_000));
+        assert_eq!(
+            InterestRateCurve::default().apr(pct(50)).unwrap(),
+            SuilendDecimal::zero()
+        );
+    }
+
+    #[test]
+    fn compounds_per_second() {
+        assert!((apr_to_apy(0.1) - 0.1_f64.exp_m1()).abs() < 1e-6);
+        assert_eq!(apr_to_apy(0.0), 0.0);
+    }
+
+    #[test]
+    fn grows_debt_from_the_obligation_rate() {
+        let accrual = BorrowAccrual {
+            borrow_rate: SuilendDecimal::one(),
+            reserve_rate: SuilendDecimal::from_bps(11_000),
+            reserve_updated_s: 1_000,
+            apr: SuilendDecimal::from_bps(1_000),
+        };
+        // Up to date with the reserve: only what accrued before it was last refreshed
+        assert_eq!(
+            accrual.growth_at(1_000).unwrap(),
+            SuilendDecimal::from_bps(11_000)
+        );
+        // A clock behind the reserve doesn't roll interest back
+        assert_eq!(
+            accrual.growth_at(0).unwrap(),
+            SuilendDecimal::from_bps(11_000)
+        );
+
+        let year_later = accrual
+            .growth_at(1_000 + SECONDS_PER_YEAR)
+            .unwrap()
+            .to_f64();
+        assert!((year_later - 1.1 * 0.1_f64.exp()).abs() < 1e-6);
+
+        let unborrowed = BorrowAccrual {
+            borrow_rate: SuilendDecimal::zero(),
+            ..accrual
+        };
+        assert_eq!(unborrowed.growth_at(5_000).unwrap(), SuilendDecimal::one());
+    }
+
+    #[test]
+    fn finds_when_interest_crosses_the_threshold() {
+        assert_eq!(
+            time_to_liquidation(&[(100.0, 0.1)], 100.0),
+            Some(Duration::ZERO)
+        );
+        // 100 at 10% continuous reaches 110 after ln(1.1) / 0.1 years
+        let expected = 1.1_f64.ln() / 0.1 * SECONDS_PER_YEAR as f64;
+        let found = time_to_liquidation(&[(60.0, 0.1), (40.0, 0.1)], 110.0)
+            .unwrap()
+            .as_secs_f64();
+        assert!(found >= expected && found - expected <= 60.0);
+    }
+
+    #[test]
+    fn gives_up_when_interest_never_crosses() {
+        assert_eq!(time_to_liquidation(&[(100.0, 0.0)], 110.0), None);
+        assert_eq!(time_to_liquidation(&[], 1.0), None);
+        // 1% for the whole 100-year horizon is e^1, short of triple
+        assert_eq!(time_to_liquidation(&[(100.0, 0.01)], 300.0), None);
+    }
+}
@@ -0,0 +1,447 @@
+use super::{
+    decimal::SuilendDecimal,
+    objects::{Borrow, CoinType, Deposit, ObjectUid, Obligation, UserReward, UserRewardManager},
+    reserves::SUILEND_PACKAGE,
+};
+use crate::errors::Errors;
+use move_core_types::u256::U256;
+use serde::Deserialize;
+use sui_sdk::{rpc_types::SuiRawMoveObject, types::base_types::ObjectID};
+
+/// Version of the `suilend::obligation` layout mirrored below, bumped whenever it changes
+pub const OBLIGATION_LAYOUT: u32 = 1;
+
+/// Move `ID` and `UID`, both a bare address in BCS
+#[derive(Deserialize)]
+struct Id([u8; 32]);
+
+impl From<Id> for ObjectID {
+    fn from(id: Id) -> Self {
+        ObjectID::new(id.0)
+    }
+}
+
+/// `suilend::decimal::Decimal`, a little-endian `u256`
+#[derive(Deserialize)]
+struct Decimal {
+    value: [u8; 32],
+}
+
+impl From<Decimal> for SuilendDecimal {
+    fn from(decimal: Decimal) -> Self {
+        SuilendDecimal::from_wad(U256::from_le_bytes(&decimal.value))
+    }
+}
+
+/// `std::type_name::TypeName`
+#[derive(Deserialize)]
+struct TypeName {
+    name: String,
+}
+
+impl From<TypeName> for CoinType {
+    fn from(type_name: TypeName) -> Self {
+        CoinType {
+            name: type_name.name,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct MoveObligation {
+    id: Id,
+    lending_market_id: Id,
+    deposits: Vec<MoveDeposit>,
+    borrows: Vec<MoveBorrow>,
+    deposited_value_usd: Decimal,
+    allowed_borrow_value_usd: Decimal,
+    unhealthy_borrow_value_usd: Decimal,
+    // Not used by the monitor, but part of the layout
+    _super_unhealthy_borrow_value_usd: Decimal,
+    unweighted_borrowed_value_usd: Decimal,
+    weighted_borrowed_value_usd: Decimal,
+    _weighted_borrowed_value_upper_bound_usd: Decimal,
+    borrowing_isolated_asset: bool,
+    user_reward_managers: Vec<MoveUserRewardManager>,
+    bad_debt_usd: Decimal,
+    closable: bool,
+}
+
+#[derive(Deserialize)]
+struct MoveDeposit {
+    coin_type: TypeName,
+    reserve_array_index: u64,
+    deposited_ctoken_amount: u64,
+    market_value: Decimal,
+    user_reward_manager_index: u64,
+    attributed_borrow_value: Decimal,
+}
+
+#[derive(Deserialize)]
+struct MoveBorrow {
+    coin_type: TypeName,
+    reserve_array_index: u64,
+    borrowed_amount: Decimal,
+    cumulative_borrow_rate: Decimal,
+    market_value: Decimal,
+    user_reward_manager_index: u64,
+}
+
+/// `suilend::liquidity_mining::UserRewardManager`
+#[derive(Deserialize)]
+struct MoveUserRewardManager {
+    pool_reward_manager_id: Id,
+    share: u64,
+    rewards: Vec<Option<MoveUserReward>>,
+    last_update_time_ms: u64,
+}
+
+#[derive(Deserialize)]
+struct MoveUserReward {
+    pool_reward_id: Id,
+    earned_rewards: Decimal,
+    cumulative_rewards_per_share: Decimal,
+}
+
+impl From<MoveObligation> for Obligation {
+    fn from(obligation: MoveObligation) -> Self {
+        Obligation {
+            id: ObjectUid {
+                id: obligation.id.into(),
+            },
+            lending_market_id: obligation.lending_market_id.into(),
+            allowed_borrow_value_usd: obligation.allowed_borrow_value_usd.into(),
+            bad_debt_usd: obligation.bad_debt_usd.into(),
+            borrowing_isolated_asset: obligation.borrowing_isolated_asset,
+            borrows: obligation.borrows.into_iter().map(Borrow::from).collect(),
+            closable: obligation.closable,
+            deposited_value_usd: obligation.deposited_value_usd.into(),
+            deposits: obligation.deposits.into_iter().map(Deposit::from).collect(),
+            unhealthy_borrow_value_usd: obligation.unhealthy_borrow_value_usd.into(),
+            unweighted_borrowed_value_usd: obligation.unweighted_borrowed_value_usd.into(),
+            weighted_borrowed_value_usd: obligation.weighted_borrowed_value_usd.into(),
+            user_reward_managers: obligation
+                .user_reward_managers
+                .into_iter()
+                .map(UserRewardManager::from)
+                .collect(),
+        }
+    }
+}
+
+impl From<MoveDeposit> for Deposit {
+    fn from(deposit: MoveDeposit) -> Self {
+        Deposit {
+            attributed_borrow_value: deposit.attributed_borrow_value.into(),
+            coin_type: deposit.coin_type.into(),
+            deposited_ctoken_amount: deposit.deposited_ctoken_amount.to_string(),
+            market_value: deposit.market_value.into(),
+            reserve_array_index: deposit.reserve_array_index.to_string(),
+            user_reward_manager_index: deposit.user_reward_manager_index.to_string(),
+        }
+    }
+}
+
+impl From<MoveBorrow> for Borrow {
+    fn from(borrow: MoveBorrow) -> Self {
+        Borrow {
+            borrowed_amount: borrow.borrowed_amount.into(),
+            coin_type: borrow.coin_type.into(),
+            cumulative_borrow_rate: borrow.cumulative_borrow_rate.into(),
+            market_value: borrow.market_value.into(),
+            reserve_array_index: borrow.reserve_array_index.to_string(),
+            user_reward_manager_index: borrow.user_reward_manager_index.to_string(),
+        }
+    }
+}
+
+impl From<MoveUserRewardManager> for UserRewardManager {
+    fn from(manager: MoveUserRewardManager) -> Self {
+        UserRewardManager {
+            pool_reward_manager_id: manager.pool_reward_manager_id.into(),
+            share: manager.share.to_string(),
+            rewards: manager
+                .rewards
+                .into_iter()
+                .map(|reward| reward.map(UserReward::from))
+                .collect(),
+            last_update_time_ms: manager.last_update_time_ms.to_string(),
+        }
+    }
+}
+
+impl From<MoveUserReward> for UserReward {
+    fn from(reward: MoveUserReward) -> Self {
+        UserReward {
+            pool_reward_id: reward.pool_reward_id.into(),
+            earned_rewards: reward.earned_rewards.into(),
+            cumulative_rewards_per_share: reward.cumulative_rewards_per_share.into(),
+        }
+    }
+}
+
+/// Decodes an `Obligation<P>` from its BCS bytes, checking it is the layout mirrored here and
+/// defined by `package` or the original Suilend package
+pub fn decode_obligation(
+    package: ObjectID,
+    object_id: ObjectID,
+    raw: &SuiRawMoveObject,
+) -> Result<Obligation, Errors> {
+    let layout = |reason: String| Errors::UnexpectedMoveLayout {
+        object_id,
+        type_name: "Obligation",
+        reason,
+    };
+    let original = ObjectID::from_hex_literal(SUILEND_PACKAGE).expect("valid Suilend package ID");
+    let address = ObjectID::from(raw.type_.address);
+    if (address != package && address != original)
+        || raw.type_.module.as_str() != "obligation"
+        || raw.type_.name.as_str() != "Obligation"
+    {
+        return Err(layout(format!("object is a `{}`", raw.type_)));
+    }
+    // Trailing bytes are rejected too, so added fields are caught as well as changed ones
+    bcs::from_bytes::<MoveObligation>(&raw.bcs_bytes)
+        .map(Obligation::from)
+        .map_err(|e| {
+            layout(format!(
+                "BCS does not match layout v{}: {}; the Suilend package may have been upgraded, \
+                 set `suilend.decode = \"json\"` meanwhile",
+                OBLIGATION_LAYOUT, e
+            ))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use move_core_types::{identifier::Identifier, language_storage::StructTag};
+    use sui_sdk::types::base_types::SequenceNumber;
+
+    const PACKAGE: ObjectID = ObjectID::new([0xaa; 32]);
+    const OBLIGATION: ObjectID = ObjectID::new([1; 32]);
+    const MARKET: ObjectID = ObjectID::new([2; 32]);
+    const SUI: &str = "0000000000000000000000000000000000000000000000000000000000000002::sui::SUI";
+    const USDC: &str =
+        "dba34672e30cb065b1f93e3ab55318768fd6fef66c15942c9f7cb846e2f900e7::usdc::USDC";
+
+    fn u64(bytes: &mut Vec<u8>, value: u64) {
+        bytes.extend(value.to_le_bytes());
+    }
+
+    /// A `Decimal` of the whole number `value`: its WAD as a 32-byte little-endian `u256`
+    fn decimal(bytes: &mut Vec<u8>, value: u64) {
+        bytes.extend((value as u128 * 10u128.pow(18)).to_le_bytes());
+        bytes.extend([0; 16]);
+    }
+
+    /// A `TypeName`: a ULEB128 length, here a single byte, then the name
+    fn type_name(bytes: &mut Vec<u8>, name: &str) {
+        bytes.push(name.len() as u8);
+        bytes.extend(name.as_bytes());
+    }
+
+    /// An obligation with one deposit, one borrow and one reward manager, written field by
+    /// field in `suilend::obligation` order rather than through the mirrored structs
+    fn obligation_bytes() -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend([1; 32]); // id
+        bytes.extend([2; 32]); // lending_market_id
+
+        bytes.push(1); // deposits
+        type_name(&mut bytes, SUI);
+        u64(&mut bytes, 0); // reserve_array_index
+        u64(&mut bytes, 3_000_000_000); // deposited_ctoken_amount
+        decimal(&mut bytes, 12); // market_value
+        u64(&mut bytes, 0); // user_reward_manager_index
+        decimal(&mut bytes, 5); // attributed_borrow_value
+
+        bytes.push(1); // borrows
+        type_name(&mut bytes, USDC);
+        u64(&mut bytes, 7); // reserve_array_index
+        decimal(&mut bytes, 5_000_000); // borrowed_amount
+        decimal(&mut bytes, 2); // cumulative_borrow_rate
+        decimal(&mut bytes, 6); // market_value
+        u64(&mut bytes, 1); // user_reward_manager_index
+
+        decimal(&mut bytes, 10); // deposited_value_usd
+        decimal(&mut bytes, 11); // allowed_borrow_value_usd
+        decimal(&mut bytes, 13); // unhealthy_borrow_value_usd
+        decimal(&mut bytes, 14); // super_unhealthy_borrow_value_usd
+        decimal(&mut bytes, 15); // unweighted_borrowed_value_usd
+        decimal(&mut bytes, 16); // weighted_borrowed_value_usd
+        decimal(&mut bytes, 17); // weighted_borrowed_value_upper_bound_usd
+        bytes.push(1); // borrowing_isolated_asset
+
+        bytes.push(1); // user_reward_managers
+        bytes.extend([3; 32]); // pool_reward_manager_id
+        u64(&mut bytes, 3_000_000_000); // share
+        bytes.push(2); // rewards
+        bytes.push(0); // None
+        bytes.push(1); // Some
+        bytes.extend([4; 32]); // pool_reward_id
+        decimal(&mut bytes, 8); // earned_rewards
+        decimal(&mut bytes, 9); // cumulative_rewards_per_share
+        u64(&mut bytes, 1_700_000_000_000); // last_update_time_ms
+
+        decimal(&mut bytes, 18); // bad_debt_usd
+        bytes.push(1); // closable
+        bytes
+    }
+
+    fn raw(address: ObjectID, module: &str, name: &str, bcs_bytes: Vec<u8>) -> SuiRawMoveObject {
+        SuiRawMoveObject {
+            type_: StructTag {
+                address: address.into(),
+                module: Identifier::new(module).unwrap(),
+                name: Identifier::new(name).unwrap(),
+                type_params: vec![],
+            },
+            has_public_transfer: false,
+            version: SequenceNumber::default(),
+            bcs_bytes,
+        }
+    }
+
+    fn decode(
+        address: ObjectID,
+        module: &str,
+        name: &str,
+        bytes: Vec<u8>,
+    ) -> Result<Obligation, Errors> {
+        decode_obligation(PACKAGE, OBLIGATION, &raw(address, module, name, bytes))
+    }
+
+    fn is_unexpected(result: Result<Obligation, Errors>) -> bool {
+        matches!(
+            result,
+            Err(Errors::UnexpectedMoveLayout { object_id, .. }) if object_id == OBLIGATION
+        )
+    }
+
+    #[test]
+    fn decodes_every_field_from_its_offset() {
+        let bytes = obligation_bytes();
+        // Two IDs, then the deposits' length and the 163 bytes of the one deposit
+        assert_eq!(bytes[64..66], [1, SUI.len() as u8]);
+        assert_eq!(bytes[64 + 1 + 163..64 + 1 + 163 + 2], [1, USDC.len() as u8]);
+        let obligation = decode(PACKAGE, "obligation", "Obligation", bytes).unwrap();
+
+        assert_eq!(obligation.id.id, OBLIGATION);
+        assert_eq!(obligation.lending_market_id, MARKET);
+
+        let deposit = &obligation.deposits[0];
+        assert_eq!(deposit.coin_type.name, SUI);
+        assert_eq!(deposit.reserve_array_index, "0");
+        assert_eq!(deposit.deposited_ctoken_amount, "3000000000");
+        assert_eq!(deposit.market_value, SuilendDecimal::from_integer(12));
+        assert_eq!(deposit.user_reward_manager_index, "0");
+        assert_eq!(
+            deposit.attributed_borrow_value,
+            SuilendDecimal::from_integer(5)
+        );
+
+        let borrow = &obligation.borrows[0];
+        assert_eq!(borrow.coin_type.name, USDC);
+        assert_eq!(borrow.reserve_array_index, "7");
+        assert_eq!(
+            borrow.borrowed_amount,
+            SuilendDecimal::from_integer(5_000_000)
+        );
+        assert_eq!(
+            borrow.cumulative_borrow_rate,
+            SuilendDecimal::from_integer(2)
+        );
+        assert_eq!(borrow.market_value, SuilendDecimal::from_integer(6));
+        assert_eq!(borrow.user_reward_manager_index, "1");
+
+        assert_eq!(
+            obligation.deposited_value_usd,
+            SuilendDecimal::from_integer(10)
+        );
+        assert_eq!(
+            obligation.allowed_borrow_value_usd,
+            SuilendDecimal::from_integer(11)
+        );
+        assert_eq!(
+            obligation.unhealthy_borrow_value_usd,
+            SuilendDecimal::from_integer(13)
+        );
+        assert_eq!(
+            obligation.unweighted_borrowed_value_usd,
+            SuilendDecimal::from_integer(15)
+        );
+        assert_eq!(
+            obligation.weighted_borrowed_value_usd,
+            SuilendDecimal::from_integer(16)
+        );
+        assert!(obligation.borrowing_isolated_asset);
+
+        let manager = &obligation.user_reward_managers[0];
+        assert_eq!(manager.pool_reward_manager_id, ObjectID::new([3; 32]));
+        assert_eq!(manager.share, "3000000000");
+        assert!(manager.rewards[0].is_none());
+        let reward = manager.rewards[1].as_ref().unwrap();
+        assert_eq!(reward.pool_reward_id, ObjectID::new([4; 32]));
+        assert_eq!(reward.earned_rewards, SuilendDecimal::from_integer(8));
+        assert_eq!(
+            reward.cumulative_rewards_per_share,
+            SuilendDecimal::from_integer(9)
+        );
+        assert_eq!(manager.last_update_time_ms, "1700000000000");
+
+        assert_eq!(obligation.bad_debt_usd, SuilendDecimal::from_integer(18));
+        assert!(obligation.closable);
+    }
+
+    #[test]
+    fn accepts_the_original_package() {
+        let original = ObjectID::from_hex_literal(SUILEND_PACKAGE).unwrap();
+        assert!(decode(original, "obligation", "Obligation", obligation_bytes()).is_ok());
+    }
+
+    #[test]
+    fn rejects_other_types() {
+        let other = ObjectID::new([0xbb; 32]);
+        assert!(is_unexpected(decode(
+            other,
+            "obligation",
+            "Obligation",
+            obligation_bytes()
+        )));
+        assert!(is_unexpected(decode(
+            PACKAGE,
+            "lending_market",
+            "Obligation",
+            obligation_bytes()
+        )));
+        assert!(is_unexpected(decode(
+            PACKAGE,
+            "obligation",
+            "ObligationOwnerCap",
+            obligation_bytes()
+        )));
+    }
+
+    #[test]
+    fn rejects_truncated_and_extended_payloads() {
+        let mut extended = obligation_bytes();
+        // A field appended by a package upgrade
+        extended.extend([0; 32]);
+        assert!(is_unexpected(decode(
+            PACKAGE,
+            "obligation",
+            "Obligation",
+            extended
+        )));
+
+        let mut truncated = obligation_bytes();
+        truncated.pop();
+        assert!(is_unexpected(decode(
+            PACKAGE,
+            "obligation",
+            "Obligation",
+            truncated
+        )));
+    }
+}
@@ -1,2 +1,15 @@
+pub mod coins;
+pub mod decimal;
+pub mod events;
+pub mod health;
+pub mod interest;
+pub mod layout;
 pub mod objects;
+pub mod plan;
+pub mod postmortem;
+pub mod reserves;
+pub mod rewards;
+pub mod sync;
+#[cfg(test)]
+pub mod testing;
 pub mod types;
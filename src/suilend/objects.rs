@@ -1,17 +1,20 @@
+use super::{coins, decimal::SuilendDecimal, layout, reserves::MarketConfig};
 use crate::errors::Errors;
-use move_core_types::language_storage::StructTag;
+use crate::rpc::RpcPool;
+use futures::{stream, Stream, TryStreamExt};
+use move_core_types::identifier::Identifier;
 use serde::{Deserialize, Serialize};
-use std::str::FromStr;
 use sui_sdk::{
     rpc_types::{
-        SuiData, SuiMoveValue, SuiObjectDataFilter, SuiObjectDataOptions, SuiObjectResponseQuery,
+        SuiData, SuiMoveValue, SuiObjectDataFilter, SuiObjectDataOptions, SuiObjectResponse,
+        SuiObjectResponseQuery,
     },
     types::{
         base_types::{ObjectID, SuiAddress},
         id::UID,
     },
-    SuiClientBuilder,
 };
+use tracing::warn;
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct SuilendAccountFields {
@@ -19,150 +22,303 @@ pub struct SuilendAccountFields {
     obligation_id: SuiAddress,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Obligation {
-    pub allowed_borrow_value_usd: BorrowValue,
-    pub bad_debt_usd: BadDebt,
+    pub id: ObjectUid,
+    pub lending_market_id: ObjectID,
+    pub allowed_borrow_value_usd: SuilendDecimal,
+    pub bad_debt_usd: SuilendDecimal,
     pub borrowing_isolated_asset: bool,
     pub borrows: Vec<Borrow>,
     pub closable: bool,
-    pub deposited_value_usd: DepositedValue,
+    pub deposited_value_usd: SuilendDecimal,
     pub deposits: Vec<Deposit>,
+    pub unhealthy_borrow_value_usd: SuilendDecimal,
+    pub unweighted_borrowed_value_usd: SuilendDecimal,
+    pub weighted_borrowed_value_usd: SuilendDecimal,
+    /// Indexed by each position's `user_reward_manager_index`
+    #[serde(default)]
+    pub user_reward_managers: Vec<UserRewardManager>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// Move `UID` as rendered by `SuiMoveStruct::to_json_value`
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct ObjectUid {
+    pub id: ObjectID,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Deposit {
-    pub attributed_borrow_value: AttributedBorrowValue,
+    pub attributed_borrow_value: SuilendDecimal,
     pub coin_type: CoinType,
     pub deposited_ctoken_amount: String,
-    pub market_value: MarketValue,
+    pub market_value: SuilendDecimal,
     pub reserve_array_index: String,
     pub user_reward_manager_index: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct AttributedBorrowValue {
-    pub value: String,
-}
-
-#[derive(Serialize, Deserialize, Debug)]
-pub struct DepositedValue {
-    pub value: String,
+impl Deposit {
+    /// `obligation_id` is the obligation holding this deposit, for the error
+    pub fn ctokens(&self, obligation_id: ObjectID) -> Result<u64, Errors> {
+        self.deposited_ctoken_amount
+            .parse::<u64>()
+            .map_err(|_| Errors::ObjectFieldParsingError {
+                object_id: obligation_id,
+                field: "deposited_ctoken_amount",
+            })
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Borrow {
-    pub borrowed_amount: BorrowedAmount,
+    pub borrowed_amount: SuilendDecimal,
     pub coin_type: CoinType,
-    pub cumulative_borrow_rate: CumulativeBorrowRate,
-    pub market_value: MarketValue,
+    pub cumulative_borrow_rate: SuilendDecimal,
+    pub market_value: SuilendDecimal,
     pub reserve_array_index: String,
     pub user_reward_manager_index: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct MarketValue {
-    pub value: String,
+/// An obligation's stake in one reserve's deposit or borrow reward pool
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UserRewardManager {
+    pub pool_reward_manager_id: ObjectID,
+    pub share: String,
+    /// Same slots as the pool's `pool_rewards`
+    pub rewards: Vec<Option<UserReward>>,
+    pub last_update_time_ms: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct CumulativeBorrowRate {
-    pub value: String,
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UserReward {
+    pub pool_reward_id: ObjectID,
+    pub earned_rewards: SuilendDecimal,
+    pub cumulative_rewards_per_share: SuilendDecimal,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct CoinType {
     pub name: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct BorrowedAmount {
-    pub value: String,
+impl CoinType {
+    /// The coin's struct name, e.g. `SUI` for `0x2::sui::SUI`
+    pub fn symbol(&self) -> &str {
+        coins::symbol(&self.name)
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct BorrowValue {
-    pub value: String,
+/// Most objects a fullnode returns from one `multi_get_objects` call
+const MULTI_GET_LIMIT: usize = 50;
+
+/// An `ObligationOwnerCap<P>` held by a watched address
+#[derive(Debug, Clone)]
+pub struct ObligationCap {
+    pub id: ObjectID,
+    /// The market's type parameter `P`
+    pub pool: String,
+    pub obligation_id: ObjectID,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct BadDebt {
-    pub value: String,
+impl ObligationCap {
+    /// `None` for the other objects of the `lending_market` module, e.g. market owner caps
+    fn from_response(response: SuiObjectResponse) -> Option<Self> {
+        let data = response.data?;
+        let cap = data.content?.try_into_move()?;
+        if cap.type_.name.as_str() != "ObligationOwnerCap" {
+            return None;
+        }
+        let pool = cap.type_.type_params.first()?.to_string();
+        match cap.fields.field_value("obligation_id")? {
+            SuiMoveValue::Address(obligation_id) => Some(Self {
+                id: data.object_id,
+                pool,
+                obligation_id: ObjectID::from(obligation_id),
+            }),
+            _ => None,
+        }
+    }
+}
+
+fn object_options() -> SuiObjectDataOptions {
+    SuiObjectDataOptions {
+        show_content: true,
+        show_type: true,
+        show_owner: false,
+        show_previous_transaction: false,
+        show_display: false,
+        show_bcs: false,
+        show_storage_rebate: false,
+    }
+}
+
+/// How obligations are read from the fullnode
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Decoding {
+    /// Reshape the fullnode's JSON rendering of the fields
+    #[default]
+    Json,
+    /// Decode the raw BCS bytes with the structs mirrored in `layout`
+    Bcs,
+}
+
+impl Decoding {
+    fn options(self) -> SuiObjectDataOptions {
+        SuiObjectDataOptions {
+            show_content: self == Decoding::Json,
+            show_bcs: self == Decoding::Bcs,
+            ..object_options()
+        }
+    }
+}
+
+fn parse_obligation(
+    market: &MarketConfig,
+    obligation_id: ObjectID,
+    response: SuiObjectResponse,
+) -> Result<Obligation, Errors> {
+    let layout = |reason: &str| Errors::UnexpectedMoveLayout {
+        object_id: obligation_id,
+        type_name: "Obligation",
+        reason: reason.to_string(),
+    };
+    let data = response.data.ok_or(Errors::ObjectNotFound(obligation_id))?;
+
+    match market.decode {
+        Decoding::Json => {
+            let obligation = data
+                .content
+                .and_then(|content| content.try_into_move())
+                .ok_or_else(|| layout("not a Move object"))?;
+            serde_json::from_value::<Obligation>(obligation.fields.to_json_value())
+                .map_err(|e| layout(&e.to_string()))
+        }
+        Decoding::Bcs => {
+            let obligation = data
+                .bcs
+                .and_then(|bcs| bcs.try_into_move())
+                .ok_or_else(|| layout("no Move object BCS"))?;
+            layout::decode_obligation(market.package_id()?, obligation_id, &obligation)
+        }
+    }
 }
 
 pub struct SuilendAccount;
 
 impl SuilendAccount {
-    pub async fn get_suilend_accounts(address: SuiAddress) -> Result<Vec<Obligation>, Errors> {
-        let client = SuiClientBuilder::default()
-            .build("https://fullnode.mainnet.sui.io:443")
-            .await?;
-
+    /// Every obligation owner cap `address` holds, across all pages of its owned objects
+    pub fn owned_caps<'a>(
+        rpc: &'a RpcPool,
+        market: &MarketConfig,
+        address: SuiAddress,
+    ) -> Result<impl Stream<Item = Result<ObligationCap, Errors>> + 'a, Errors> {
         let query = SuiObjectResponseQuery::new(
-            Some(SuiObjectDataFilter::StructType(
-                    StructTag::from_str("0xf95b06141ed4a174f239417323bde3f209b972f5930d8521ea38a52aff3a6ddf::lending_market::ObligationOwnerCap<0xf95b06141ed4a174f239417323bde3f209b972f5930d8521ea38a52aff3a6ddf::suilend::MAIN_POOL>").unwrap(),
-            )),
-            Some(SuiObjectDataOptions {
-                show_content: true,
-                show_type: true,
-                show_owner: false,
-                show_previous_transaction: false,
-                show_display: false,
-                show_bcs: false,
-                show_storage_rebate: false,
-            })
+            Some(SuiObjectDataFilter::MoveModule {
+                package: market.package_id()?,
+                module: Identifier::new("lending_market").expect("valid Move identifier"),
+            }),
+            Some(object_options()),
         );
 
-        let suilend_account = client
-            .read_api()
-            .get_owned_objects(address, Some(query), None, None)
-            .await?
-            .data;
+        // The state is the cursor of the next page, `None` once the last page was read
+        let pages = stream::try_unfold(Some(None), move |cursor: Option<Option<ObjectID>>| {
+            let query = query.clone();
+            async move {
+                let Some(cursor) = cursor else {
+                    return Ok(None);
+                };
+                let page = rpc
+                    .call(|client| {
+                        let query = query.clone();
+                        async move {
+                            Ok(client
+                                .read_api()
+                                .get_owned_objects(address, Some(query), cursor, None)
+                                .await?)
+                        }
+                    })
+                    .await?;
+                let next = match page.next_cursor {
+                    Some(next) if page.has_next_page => Some(Some(next)),
+                    _ => None,
+                };
+                Ok::<_, Errors>(Some((page.data, next)))
+            }
+        });
 
-        let mut obligations: Vec<Obligation> = Vec::new();
+        Ok(pages
+            .map_ok(|objects| {
+                stream::iter(
+                    objects
+                        .into_iter()
+                        .filter_map(ObligationCap::from_response)
+                        .map(Ok),
+                )
+            })
+            .try_flatten())
+    }
 
-        for e in suilend_account.into_iter() {
-            let data = e
-                .data
-                .unwrap()
-                .content
-                .unwrap()
-                .try_as_move()
-                .unwrap()
-                .fields
-                .field_value("obligation_id")
-                .unwrap();
-
-            if let SuiMoveValue::Address(obligation_id) = data {
-                let obligation = client
-                    .read_api()
-                    .get_object_with_options(
-                        ObjectID::from(obligation_id),
-                        SuiObjectDataOptions {
-                            show_content: true,
-                            show_type: true,
-                            show_owner: false,
-                            show_previous_transaction: false,
-                            show_display: false,
-                            show_bcs: false,
-                            show_storage_rebate: false,
-                        },
-                    )
-                    .await?;
+    /// Obligations behind every `ObligationOwnerCap<P>` `address` holds, in any watched pool
+    pub async fn get_suilend_accounts(
+        rpc: &RpcPool,
+        market: &MarketConfig,
+        address: SuiAddress,
+    ) -> Result<Vec<Obligation>, Errors> {
+        let caps = Self::owned_caps(rpc, market, address)?;
+        futures::pin_mut!(caps);
+
+        let mut obligation_ids = Vec::new();
+        while let Some(cap) = caps.try_next().await? {
+            if market.watches_pool(&cap.pool) {
+                obligation_ids.push(cap.obligation_id);
+            }
+        }
+        Self::get_obligations(rpc, market, &obligation_ids).await
+    }
 
-                let sample = obligation
-                    .data
-                    .unwrap()
-                    .content
-                    .unwrap()
-                    .try_into_move()
-                    .unwrap();
-                let json = sample.fields.to_json_value();
-                let obligation = serde_json::from_value::<Obligation>(json).unwrap();
-                obligations.push(obligation);
+    /// Reads obligations in as few requests as the fullnode allows; ones that fail to parse are skipped
+    pub async fn get_obligations(
+        rpc: &RpcPool,
+        market: &MarketConfig,
+        obligation_ids: &[ObjectID],
+    ) -> Result<Vec<Obligation>, Errors> {
+        let decoding = market.decode;
+        let mut obligations = Vec::with_capacity(obligation_ids.len());
+        for ids in obligation_ids.chunks(MULTI_GET_LIMIT) {
+            let responses = rpc
+                .call(|client| async move {
+                    Ok(client
+                        .read_api()
+                        .multi_get_object_with_options(ids.to_vec(), decoding.options())
+                        .await?)
+                })
+                .await?;
+            // Responses come back in request order
+            for (id, response) in ids.iter().zip(responses) {
+                match parse_obligation(market, *id, response) {
+                    Ok(obligation) => obligations.push(obligation),
+                    Err(e) => warn!("Skipping obligation: {}", e),
+                }
             }
         }
-        println!("{:?}", &obligations);
         Ok(obligations)
     }
+
+    pub async fn get_obligation(
+        rpc: &RpcPool,
+        market: &MarketConfig,
+        obligation_id: ObjectID,
+    ) -> Result<Obligation, Errors> {
+        let decoding = market.decode;
+        let response = rpc
+            .call(|client| async move {
+                Ok(client
+                    .read_api()
+                    .get_object_with_options(obligation_id, decoding.options())
+                    .await?)
+            })
+            .await?;
+        parse_obligation(market, obligation_id, response)
+    }
 }
@@ -0,0 +1,93 @@
+use super::{
+    health::TRACKED_OBLIGATIONS,
+    interest::unix_now,
+    reserves::{Markets, ReserveRegistry, RewardInfo},
+};
+use crate::pyth::types::FeedId;
+use std::{collections::BTreeSet, fmt};
+
+/// The exact set of feeds the watched obligations need, each listed once
+#[derive(Debug, Default, PartialEq)]
+pub struct FeedPlan {
+    /// Feeds pricing a deposit or borrow
+    pub positions: BTreeSet<FeedId>,
+    /// Feeds pricing active rewards, only planned when asked for
+    pub rewards: BTreeSet<FeedId>,
+    /// Reward coins no feed could be found for
+    pub unpriced: BTreeSet<String>,
+}
+
+impl FeedPlan {
+    pub fn build(markets: &Markets, include_rewards: bool) -> Self {
+        let mut plan = Self::default();
+        let now_ms = unix_now() * 1000;
+
+        for tracked in TRACKED_OBLIGATIONS.iter() {
+            plan.positions.extend(
+                tracked
+                    .deposits
+                    .iter()
+                    .map(|d| d.leg.feed)
+                    .chain(tracked.borrows.iter().map(|b| b.leg.feed)),
+            );
+            if !include_rewards {
+                continue;
+            }
+            let Ok(reserves) = markets.get(&tracked.obligation.lending_market_id) else {
+                continue;
+            };
+
+            let deposit_rewards = tracked
+                .obligation
+                .deposits
+                .iter()
+                .filter_map(|d| reserves.by_index(&d.reserve_array_index).ok())
+                .flat_map(|reserve| reserve.deposit_rewards.iter());
+            let borrow_rewards = tracked
+                .obligation
+                .borrows
+                .iter()
+                .filter_map(|b| reserves.by_index(&b.reserve_array_index).ok())
+                .flat_map(|reserve| reserve.borrow_rewards.iter());
+            for reward in deposit_rewards.chain(borrow_rewards) {
+                plan.add_reward(&reserves, reward, now_ms);
+            }
+        }
+        plan.rewards.retain(|feed| !plan.positions.contains(feed));
+        plan
+    }
+
+    fn add_reward(&mut self, reserves: &ReserveRegistry, reward: &RewardInfo, now_ms: u64) {
+        if !reward.is_active(now_ms) {
+            return;
+        }
+        match reserves.feed_for(&reward.coin_type) {
+            Some(feed) => {
+                self.rewards.insert(feed);
+            }
+            None => {
+                self.unpriced.insert(reward.coin_type.clone());
+            }
+        }
+    }
+
+    pub fn feeds(&self) -> Vec<FeedId> {
+        self.positions.union(&self.rewards).copied().collect()
+    }
+}
+
+impl fmt::Display for FeedPlan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} position feeds, {} reward feeds",
+            self.positions.len(),
+            self.rewards.len()
+        )?;
+        if !self.unpriced.is_empty() {
+            let coins = self.unpriced.iter().cloned().collect::<Vec<_>>();
+            write!(f, ", unpriced rewards: {}", coins.join(", "))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,285 @@
+use super::{
+    decimal::SuilendDecimal,
+    events::{LiquidateEvent, SuilendEvent},
+    health::TRACKED_OBLIGATIONS,
+    interest::unix_now,
+    reserves::{ReserveInfo, ReserveRegistry},
+};
+use crate::{
+    errors::Errors,
+    history::{HEALTH_HISTORY, PRICE_HISTORY},
+    pyth::{registry, types::FeedId},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+use sui_sdk::types::base_types::{ObjectID, SuiAddress};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PostMortemConfig {
+    /// Where liquidation reports are written
+    pub dir: PathBuf,
+    /// How much price and health history is kept for reports
+    pub history_secs: u64,
+}
+
+impl Default for PostMortemConfig {
+    fn default() -> Self {
+        Self {
+            dir: PathBuf::from("postmortems"),
+            history_secs: 3600,
+        }
+    }
+}
+
+/// An amount of one coin, valued at our last price before the liquidation
+#[derive(Debug, Clone, Serialize)]
+pub struct Amount {
+    pub coin: String,
+    pub tokens: f64,
+    pub price_usd: Option<f64>,
+    pub value_usd: Option<f64>,
+}
+
+impl Amount {
+    fn new(coin: &str, tokens: SuilendDecimal, price_usd: Option<f64>) -> Self {
+        let tokens = tokens.to_f64();
+        Self {
+            coin: coin.to_string(),
+            tokens,
+            price_usd,
+            value_usd: price_usd.map(|price| price * tokens),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthPoint {
+    pub at: u64,
+    pub health_ratio: f64,
+}
+
+/// What happened in a liquidation of one of our obligations and how we got there
+#[derive(Debug, Clone, Serialize)]
+pub struct PostMortem {
+    pub obligation_id: ObjectID,
+    pub label: String,
+    pub liquidator: SuiAddress,
+    pub tx_digest: String,
+    /// Unix seconds
+    pub liquidated_at: u64,
+    pub repaid: Amount,
+    pub seized: Amount,
+    pub liquidator_bonus: Amount,
+    pub protocol_fee: Amount,
+    /// Bonus and protocol fee together, what the liquidation cost us beyond the repaid debt
+    pub penalty_usd: Option<f64>,
+    /// Our last price of every feed the obligation used
+    pub prices: BTreeMap<String, f64>,
+    pub health: Vec<HealthPoint>,
+}
+
+fn raw_amount(field: &'static str, value: &str) -> Result<u64, Errors> {
+    value
+        .parse::<u64>()
+        .map_err(|_| Errors::MoveObjectParsingError(field))
+}
+
+fn price_at(feed: &FeedId, at: u64) -> Option<f64> {
+    PRICE_HISTORY
+        .get(feed)
+        .and_then(|history| history.at(at))
+        .map(|price| price.to_f64())
+}
+
+fn ctokens(
+    reserve: &ReserveInfo,
+    field: &'static str,
+    value: &str,
+) -> Result<SuilendDecimal, Errors> {
+    reserve.to_tokens(reserve.ctokens_to_underlying(raw_amount(field, value)?)?)
+}
+
+impl PostMortem {
+    /// Builds the report from `event`; call before the obligation is re-synced
+    pub fn new(
+        label: &str,
+        event: &SuilendEvent,
+        liquidation: &LiquidateEvent,
+        reserves: &ReserveRegistry,
+        history_secs: u64,
+    ) -> Result<Self, Errors> {
+        let liquidated_at = event
+            .timestamp_ms
+            .map(|ms| ms / 1000)
+            .unwrap_or_else(unix_now);
+
+        let repay_reserve = reserves.by_coin(&liquidation.repay_coin_type.name)?;
+        let withdraw_reserve = reserves.by_coin(&liquidation.withdraw_coin_type.name)?;
+        let repay_price = price_at(&repay_reserve.feed()?, liquidated_at);
+        let withdraw_price = price_at(&withdraw_reserve.feed()?, liquidated_at);
+        let repay_coin = liquidation.repay_coin_type.symbol();
+        let withdraw_coin = liquidation.withdraw_coin_type.symbol();
+
+        let repaid = Amount::new(
+            repay_coin,
+            repay_reserve.to_tokens(SuilendDecimal::from_integer(raw_amount(
+                "repay_amount",
+                &liquidation.repay_amount,
+            )?))?,
+            repay_price,
+        );
+        let seized = Amount::new(
+            withdraw_coin,
+            ctokens(
+                withdraw_reserve,
+                "withdraw_amount",
+                &liquidation.withdraw_amount,
+            )?,
+            withdraw_price,
+        );
+        let liquidator_bonus = Amount::new(
+            withdraw_coin,
+            ctokens(
+                withdraw_reserve,
+                "liquidator_bonus_amount",
+                &liquidation.liquidator_bonus_amount,
+            )?,
+            withdraw_price,
+        );
+        let protocol_fee = Amount::new(
+            withdraw_coin,
+            ctokens(
+                withdraw_reserve,
+                "protocol_fee_amount",
+                &liquidation.protocol_fee_amount,
+            )?,
+            withdraw_price,
+        );
+        let penalty_usd = liquidator_bonus
+            .value_usd
+            .zip(protocol_fee.value_usd)
+            .map(|(bonus, fee)| bonus + fee);
+
+        let feeds = TRACKED_OBLIGATIONS
+            .get(&liquidation.obligation_id)
+            .map(|tracked| {
+                tracked
+                    .deposits
+                    .iter()
+                    .map(|d| d.leg.feed)
+                    .chain(tracked.borrows.iter().map(|b| b.leg.feed))
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        let prices = feeds
+            .iter()
+            .filter_map(|feed| Some((registry::name(feed), price_at(feed, liquidated_at)?)))
+            .collect();
+
+        let health = HEALTH_HISTORY
+            .get(&liquidation.obligation_id)
+            .map(|history| {
+                history
+                    .between(liquidated_at.saturating_sub(history_secs), liquidated_at)
+                    .into_iter()
+                    .map(|(at, health_ratio)| HealthPoint { at, health_ratio })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Ok(Self {
+            obligation_id: liquidation.obligation_id,
+            label: label.to_string(),
+            liquidator: event.sender,
+            tx_digest: event.id.tx_digest.to_string(),
+            liquidated_at,
+            repaid,
+            seized,
+            liquidator_bonus,
+            protocol_fee,
+            penalty_usd,
+            prices,
+            health,
+        })
+    }
+
+    pub fn to_json(&self) -> Result<String, Errors> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let usd = |value: Option<f64>| {
+            value
+                .map(|v| format!("${:.2}", v))
+                .unwrap_or_else(|| "unknown".to_string())
+        };
+        let mut md = String::new();
+        let _ = writeln!(md, "# Liquidation of {}\n", self.label);
+        let _ = writeln!(md, "- Obligation: `{}`", self.obligation_id);
+        let _ = writeln!(md, "- Transaction: `{}`", self.tx_digest);
+        let _ = writeln!(md, "- Time: {} (unix)", self.liquidated_at);
+        let _ = writeln!(md, "- Liquidator: `{}`", self.liquidator);
+        let _ = writeln!(md, "- Penalty paid: {}\n", usd(self.penalty_usd));
+
+        let _ = writeln!(md, "| | Coin | Amount | Price | Value |");
+        let _ = writeln!(md, "|---|---|---|---|---|");
+        for (name, amount) in [
+            ("Repaid", &self.repaid),
+            ("Seized", &self.seized),
+            ("Liquidator bonus", &self.liquidator_bonus),
+            ("Protocol fee", &self.protocol_fee),
+        ] {
+            let _ = writeln!(
+                md,
+                "| {} | {} | {:.6} | {} | {} |",
+                name,
+                amount.coin,
+                amount.tokens,
+                usd(amount.price_usd),
+                usd(amount.value_usd)
+            );
+        }
+
+        let _ = writeln!(md, "\n## Prices at liquidation\n");
+        let _ = writeln!(md, "| Feed | Price |");
+        let _ = writeln!(md, "|---|---|");
+        for (feed, price) in self.prices.iter() {
+            let _ = writeln!(md, "| {} | {} |", feed, price);
+        }
+
+        let _ = writeln!(md, "\n## Health before liquidation\n");
+        if self.health.is_empty() {
+            let _ = writeln!(md, "No health history was recorded.");
+            return md;
+        }
+        let _ = writeln!(md, "| Minutes before | Health ratio |");
+        let _ = writeln!(md, "|---|---|");
+        // Last sample of each minute keeps the table readable
+        let mut by_minute = BTreeMap::new();
+        for point in self.health.iter() {
+            by_minute.insert(
+                self.liquidated_at.saturating_sub(point.at) / 60,
+                point.health_ratio,
+            );
+        }
+        for (minutes, ratio) in by_minute.iter().rev() {
+            let _ = writeln!(md, "| {} | {:.4} |", minutes, ratio);
+        }
+        md
+    }
+
+    /// Writes `<obligation>-<time>.json` and `.md` into `dir`, returning the Markdown path
+    pub fn write(&self, dir: &Path) -> Result<PathBuf, Errors> {
+        std::fs::create_dir_all(dir)?;
+        let stem = dir.join(format!("{}-{}", self.obligation_id, self.liquidated_at));
+        std::fs::write(stem.with_extension("json"), self.to_json()?)?;
+        let markdown = stem.with_extension("md");
+        std::fs::write(&markdown, self.to_markdown())?;
+        Ok(markdown)
+    }
+}
@@ -0,0 +1,617 @@
+use super::{
+    coins,
+    decimal::SuilendDecimal,
+    events::ReserveRefreshEvent,
+    interest::{BorrowAccrual, InterestRateCurve},
+    objects::{CoinType, Decoding, ObjectUid},
+    types::{BasisPoints, Bw, CloseLtv, OpenLtv},
+};
+use crate::{
+    errors::Errors,
+    pyth::{prices::pow10, types::FeedId},
+    rpc::RpcPool,
+};
+use dashmap::DashMap;
+use move_core_types::language_storage::StructTag;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    str::FromStr,
+    sync::Arc,
+};
+use sui_sdk::{
+    rpc_types::{SuiData, SuiObjectDataOptions},
+    types::base_types::ObjectID,
+};
+use tracing::{info, warn};
+
+/// Original Suilend package, where its types are defined
+pub const SUILEND_PACKAGE: &str =
+    "0xf95b06141ed4a174f239417323bde3f209b972f5930d8521ea38a52aff3a6ddf";
+
+/// Which Suilend deployment to monitor, every lending market of it by default
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MarketConfig {
+    pub package: String,
+    /// Market type parameters to restrict to, e.g. `0x...::suilend::MAIN_POOL`; empty for all
+    pub pools: Vec<String>,
+    /// Coin type → Pyth feed ID, for reserves whose on-chain price identifier is unusable
+    pub coin_feeds: BTreeMap<String, FeedId>,
+    /// How obligations are decoded, `json` or `bcs`
+    pub decode: Decoding,
+}
+
+impl Default for MarketConfig {
+    fn default() -> Self {
+        Self {
+            package: SUILEND_PACKAGE.to_string(),
+            pools: Vec::new(),
+            coin_feeds: BTreeMap::new(),
+            decode: Decoding::default(),
+        }
+    }
+}
+
+impl MarketConfig {
+    pub fn pool_type(pool: &str) -> Result<StructTag, Errors> {
+        StructTag::from_str(pool).map_err(|_| Errors::InvalidStructTag(pool.to_string()))
+    }
+
+    /// Whether obligations in `LendingMarket<pool>` should be watched
+    pub fn watches_pool(&self, pool: &str) -> bool {
+        let pool = coins::normalize(pool);
+        self.pools.is_empty() || self.pools.iter().any(|p| coins::normalize(p) == pool)
+    }
+
+    pub fn package_id(&self) -> Result<ObjectID, Errors> {
+        ObjectID::from_hex_literal(&self.package)
+            .map_err(|_| Errors::InvalidObjectId(self.package.clone()))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct LendingMarket {
+    pub reserves: Vec<Reserve>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Reserve {
+    pub array_index: String,
+    pub coin_type: CoinType,
+    pub config: ReserveConfigCell,
+    pub mint_decimals: u8,
+    pub price_identifier: PriceIdentifier,
+    pub available_amount: String,
+    pub ctoken_supply: String,
+    pub borrowed_amount: SuilendDecimal,
+    pub unclaimed_spread_fees: SuilendDecimal,
+    pub cumulative_borrow_rate: SuilendDecimal,
+    pub interest_last_update_timestamp_s: String,
+    #[serde(default)]
+    pub deposits_pool_reward_manager: PoolRewardManager,
+    #[serde(default)]
+    pub borrows_pool_reward_manager: PoolRewardManager,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct PoolRewardManager {
+    pub id: Option<ObjectUid>,
+    pub total_shares: String,
+    /// Slots of finished and removed rewards are `None`
+    pub pool_rewards: Vec<Option<PoolReward>>,
+    pub last_update_time_ms: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PoolReward {
+    pub id: ObjectUid,
+    pub coin_type: CoinType,
+    pub start_time_ms: String,
+    pub end_time_ms: String,
+    pub total_rewards: String,
+    pub cumulative_rewards_per_share: SuilendDecimal,
+}
+
+/// `Cell<ReserveConfig>`
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ReserveConfigCell {
+    pub element: ReserveConfig,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ReserveConfig {
+    pub open_ltv_pct: u8,
+    pub close_ltv_pct: u8,
+    pub borrow_weight_bps: String,
+    /// Share of borrow interest kept by the protocol
+    pub spread_fee_bps: String,
+    /// Utilization breakpoints in percent
+    pub interest_rate_utils: Vec<u8>,
+    /// Borrow APR at each breakpoint in basis points
+    pub interest_rate_aprs: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PriceIdentifier {
+    pub bytes: Vec<u8>,
+}
+
+impl PriceIdentifier {
+    /// `None` for reserves created without a Pyth feed, which store zeroes
+    pub fn feed_id(&self) -> Option<FeedId> {
+        let bytes = <[u8; 32]>::try_from(self.bytes.as_slice()).ok()?;
+        (bytes != [0; 32]).then(|| FeedId::from_bytes(bytes))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ReserveInfo {
+    pub array_index: u64,
+    pub coin_type: String,
+    pub open_ltv: OpenLtv,
+    pub close_ltv: CloseLtv,
+    pub borrow_weight: Bw,
+    pub price_identifier: Option<FeedId>,
+    pub mint_decimals: u8,
+    /// Underlying units per cToken
+    pub ctoken_ratio: SuilendDecimal,
+    pub cumulative_borrow_rate: SuilendDecimal,
+    pub interest_last_update_timestamp_s: u64,
+    pub interest_rate: InterestRateCurve,
+    /// Borrowed share of the total supply
+    pub utilization: SuilendDecimal,
+    pub spread_fee: SuilendDecimal,
+    pub deposit_rewards: RewardPool,
+    pub borrow_rewards: RewardPool,
+}
+
+/// The liquidity mining programs of one side of a reserve, `liquidity_mining::PoolRewardManager`
+#[derive(Debug, Clone, Default)]
+pub struct RewardPool {
+    pub id: Option<ObjectID>,
+    pub total_shares: u64,
+    pub last_update_time_ms: u64,
+    /// Slot `i` lines up with slot `i` of every `UserRewardManager::rewards`
+    pub rewards: Vec<Option<RewardInfo>>,
+}
+
+impl RewardPool {
+    pub fn iter(&self) -> impl Iterator<Item = &RewardInfo> {
+        self.rewards.iter().flatten()
+    }
+
+    /// `cumulative_rewards_per_share` of `reward` once `update_pool_reward_manager` ran at `now_ms`
+    pub fn cumulative_at(
+        &self,
+        reward: &RewardInfo,
+        now_ms: u64,
+    ) -> Result<SuilendDecimal, Errors> {
+        if self.total_shares == 0
+            || now_ms <= self.last_update_time_ms
+            || now_ms < reward.start_time_ms
+            || self.last_update_time_ms >= reward.end_time_ms
+            || reward.end_time_ms <= reward.start_time_ms
+        {
+            return Ok(reward.cumulative_rewards_per_share);
+        }
+        let elapsed_ms =
+            now_ms.min(reward.end_time_ms) - reward.start_time_ms.max(self.last_update_time_ms);
+        let unlocked = SuilendDecimal::from_integer(reward.total_rewards)
+            .checked_mul(SuilendDecimal::from_integer(elapsed_ms))?
+            .checked_div(SuilendDecimal::from_integer(
+                reward.end_time_ms - reward.start_time_ms,
+            ))?;
+        reward
+            .cumulative_rewards_per_share
+            .checked_add(unlocked.checked_div(SuilendDecimal::from_integer(self.total_shares))?)
+    }
+}
+
+/// A liquidity mining program paying `coin_type` to depositors or borrowers of a reserve
+#[derive(Debug, Clone)]
+pub struct RewardInfo {
+    pub id: ObjectID,
+    pub coin_type: String,
+    pub start_time_ms: u64,
+    pub end_time_ms: u64,
+    /// Raw units paid out over the whole program
+    pub total_rewards: u64,
+    pub cumulative_rewards_per_share: SuilendDecimal,
+}
+
+impl RewardInfo {
+    pub fn is_active(&self, now_ms: u64) -> bool {
+        (self.start_time_ms..self.end_time_ms).contains(&now_ms)
+    }
+}
+
+impl ReserveInfo {
+    /// Underlying raw units backing `ctokens`
+    pub fn ctokens_to_underlying(&self, ctokens: u64) -> Result<SuilendDecimal, Errors> {
+        SuilendDecimal::from_integer(ctokens).checked_mul(self.ctoken_ratio)
+    }
+
+    /// Raw units to whole tokens using `mint_decimals`
+    pub fn to_tokens(&self, raw: SuilendDecimal) -> Result<SuilendDecimal, Errors> {
+        Ok(SuilendDecimal::from_wad(
+            raw.as_wad() / pow10(self.mint_decimals as u32)?,
+        ))
+    }
+
+    /// The reserve's own Pyth feed, else the fallback table's
+    pub fn feed(&self) -> Result<FeedId, Errors> {
+        self.price_identifier
+            .or_else(|| coins::fallback(&self.coin_type))
+            .ok_or_else(|| Errors::UnknownCoin(self.coin_type.clone()))
+    }
+
+    pub fn current_apr(&self) -> Result<SuilendDecimal, Errors> {
+        self.interest_rate.apr(self.utilization)
+    }
+
+    /// What depositors earn: borrow interest spread over the supply, less the protocol's cut
+    pub fn supply_apr(&self) -> Result<SuilendDecimal, Errors> {
+        self.current_apr()?
+            .checked_mul(self.utilization)?
+            .checked_mul(SuilendDecimal::one().saturating_sub(self.spread_fee))
+    }
+
+    /// Takes the balances and borrow rate a reserve compounded its interest to at `timestamp_s`
+    pub fn refresh(
+        &mut self,
+        refresh: &ReserveRefreshEvent,
+        timestamp_s: u64,
+    ) -> Result<(), Errors> {
+        let supply = Supply::new(
+            parse_u64("available_amount", &refresh.available_amount)?,
+            refresh.borrowed_amount,
+            refresh.unclaimed_spread_fees,
+            parse_u64("ctoken_supply", &refresh.ctoken_supply)?,
+        )?;
+        self.ctoken_ratio = supply.ctoken_ratio;
+        self.utilization = supply.utilization;
+        self.cumulative_borrow_rate = refresh.cumulative_borrow_rate;
+        self.interest_last_update_timestamp_s = timestamp_s;
+        Ok(())
+    }
+
+    /// Interest projection for a borrow last refreshed at `borrow_rate`
+    pub fn accrual(&self, borrow_rate: SuilendDecimal) -> Result<BorrowAccrual, Errors> {
+        Ok(BorrowAccrual {
+            borrow_rate,
+            reserve_rate: self.cumulative_borrow_rate,
+            reserve_updated_s: self.interest_last_update_timestamp_s,
+            apr: self.current_apr()?,
+        })
+    }
+}
+
+/// What a reserve's balances imply for its cToken exchange rate and utilization
+struct Supply {
+    ctoken_ratio: SuilendDecimal,
+    utilization: SuilendDecimal,
+}
+
+impl Supply {
+    fn new(
+        available_amount: u64,
+        borrowed_amount: SuilendDecimal,
+        unclaimed_spread_fees: SuilendDecimal,
+        ctoken_supply: u64,
+    ) -> Result<Self, Errors> {
+        // total supply = available + borrowed - unclaimed spread fees
+        let total_supply = SuilendDecimal::from_integer(available_amount)
+            .checked_add(borrowed_amount)?
+            .saturating_sub(unclaimed_spread_fees);
+        Ok(Self {
+            ctoken_ratio: if ctoken_supply > 0 {
+                total_supply.checked_div(SuilendDecimal::from_integer(ctoken_supply))?
+            } else {
+                SuilendDecimal::one()
+            },
+            utilization: if total_supply.is_zero() {
+                SuilendDecimal::zero()
+            } else {
+                borrowed_amount.checked_div(total_supply)?
+            },
+        })
+    }
+}
+
+fn parse_u64(field: &'static str, value: &str) -> Result<u64, Errors> {
+    value
+        .parse::<u64>()
+        .map_err(|_| Errors::MoveObjectParsingError(field))
+}
+
+fn rewards(manager: PoolRewardManager) -> Result<RewardPool, Errors> {
+    // Reserves without the manager decode to its empty default
+    let parse = |field, value: &str| match value {
+        "" => Ok(0),
+        value => parse_u64(field, value),
+    };
+    Ok(RewardPool {
+        id: manager.id.map(|uid| uid.id),
+        total_shares: parse("total_shares", &manager.total_shares)?,
+        last_update_time_ms: parse("last_update_time_ms", &manager.last_update_time_ms)?,
+        rewards: manager
+            .pool_rewards
+            .into_iter()
+            .map(|slot| {
+                slot.map(|reward| {
+                    Ok(RewardInfo {
+                        id: reward.id.id,
+                        coin_type: reward.coin_type.name,
+                        start_time_ms: parse_u64("start_time_ms", &reward.start_time_ms)?,
+                        end_time_ms: parse_u64("end_time_ms", &reward.end_time_ms)?,
+                        total_rewards: parse_u64("total_rewards", &reward.total_rewards)?,
+                        cumulative_rewards_per_share: reward.cumulative_rewards_per_share,
+                    })
+                })
+                .transpose()
+            })
+            .collect::<Result<_, Errors>>()?,
+    })
+}
+
+impl TryFrom<Reserve> for ReserveInfo {
+    type Error = Errors;
+
+    fn try_from(reserve: Reserve) -> Result<Self, Self::Error> {
+        let config = reserve.config.element;
+        let borrow_weight_bps = config
+            .borrow_weight_bps
+            .parse::<u32>()
+            .map_err(|_| Errors::MoveObjectParsingError("borrow_weight_bps"))?;
+
+        let interest_rate = InterestRateCurve {
+            utils: config.interest_rate_utils,
+            aprs: config
+                .interest_rate_aprs
+                .iter()
+                .map(|apr| apr.parse::<u64>())
+                .collect::<Result<_, _>>()
+                .map_err(|_| Errors::MoveObjectParsingError("interest_rate_aprs"))?,
+        };
+
+        let supply = Supply::new(
+            parse_u64("available_amount", &reserve.available_amount)?,
+            reserve.borrowed_amount,
+            reserve.unclaimed_spread_fees,
+            parse_u64("ctoken_supply", &reserve.ctoken_supply)?,
+        )?;
+
+        Ok(Self {
+            array_index: reserve
+                .array_index
+                .parse::<u64>()
+                .map_err(|_| Errors::MoveObjectParsingError("array_index"))?,
+            coin_type: reserve.coin_type.name,
+            open_ltv: OpenLtv(BasisPoints::from_pct(config.open_ltv_pct)),
+            close_ltv: CloseLtv(BasisPoints::from_pct(config.close_ltv_pct)),
+            borrow_weight: Bw(BasisPoints(borrow_weight_bps)),
+            price_identifier: reserve.price_identifier.feed_id(),
+            mint_decimals: reserve.mint_decimals,
+            ctoken_ratio: supply.ctoken_ratio,
+            cumulative_borrow_rate: reserve.cumulative_borrow_rate,
+            interest_last_update_timestamp_s: reserve
+                .interest_last_update_timestamp_s
+                .parse::<u64>()
+                .map_err(|_| Errors::MoveObjectParsingError("interest_last_update_timestamp_s"))?,
+            interest_rate,
+            utilization: supply.utilization,
+            spread_fee: SuilendDecimal::from_bps(parse_u64(
+                "spread_fee_bps",
+                &config.spread_fee_bps,
+            )?),
+            deposit_rewards: rewards(reserve.deposits_pool_reward_manager)?,
+            borrow_rewards: rewards(reserve.borrows_pool_reward_manager)?,
+        })
+    }
+}
+
+/// Reserve configs of every lending market a watched obligation lives in.
+///
+/// Clones share one registry, so reserves re-read by the sync task are the ones the
+/// monitor values positions, rewards and post-mortems with.
+#[derive(Debug, Clone, Default)]
+pub struct Markets(Arc<DashMap<ObjectID, Arc<ReserveRegistry>>>);
+
+impl Markets {
+    pub fn get(&self, lending_market_id: &ObjectID) -> Result<Arc<ReserveRegistry>, Errors> {
+        self.0
+            .get(lending_market_id)
+            .map(|reserves| reserves.clone())
+            .ok_or(Errors::MarketNotLoaded(*lending_market_id))
+    }
+
+    /// Loads the market's reserves the first time it is seen
+    pub async fn ensure(
+        &self,
+        rpc: &RpcPool,
+        lending_market_id: ObjectID,
+    ) -> Result<Arc<ReserveRegistry>, Errors> {
+        if let Ok(reserves) = self.get(&lending_market_id) {
+            return Ok(reserves);
+        }
+        let reserves = self.reload(rpc, lending_market_id).await?;
+        info!("Loaded lending market {}", lending_market_id);
+        Ok(reserves)
+    }
+
+    /// Re-reads one market's reserves, replacing them for every clone
+    pub async fn reload(
+        &self,
+        rpc: &RpcPool,
+        lending_market_id: ObjectID,
+    ) -> Result<Arc<ReserveRegistry>, Errors> {
+        let reserves = Arc::new(ReserveRegistry::load(rpc, lending_market_id).await?);
+        self.0.insert(lending_market_id, reserves.clone());
+        Ok(reserves)
+    }
+
+    /// Applies an interest refresh to the reserve it names, returning the refreshed reserve.
+    ///
+    /// `None` when the market isn't loaded or the refresh is older than what is cached.
+    pub fn apply_refresh(
+        &self,
+        refresh: &ReserveRefreshEvent,
+        timestamp_s: u64,
+    ) -> Result<Option<ReserveInfo>, Errors> {
+        let Some(mut entry) = self.0.get_mut(&refresh.lending_market_id) else {
+            return Ok(None);
+        };
+        let mut registry = ReserveRegistry::clone(&entry);
+        let index = registry.by_coin(&refresh.coin_type.name)?.array_index;
+        let Some(reserve) = registry.0.get_mut(&index) else {
+            return Ok(None);
+        };
+        if timestamp_s < reserve.interest_last_update_timestamp_s {
+            return Ok(None);
+        }
+        reserve.refresh(refresh, timestamp_s)?;
+        let reserve = reserve.clone();
+        *entry = Arc::new(registry);
+        Ok(Some(reserve))
+    }
+
+    /// Re-reads every known market, keeping the old reserves of any that fail
+    pub async fn refresh(&self, rpc: &RpcPool) {
+        let ids = self.0.iter().map(|entry| *entry.key()).collect::<Vec<_>>();
+        for id in ids {
+            if let Err(e) = self.reload(rpc, id).await {
+                warn!("Keeping cached reserves of market {}: {}", id, e);
+            }
+        }
+    }
+
+    /// A snapshot of the loaded markets
+    pub fn iter(&self) -> impl Iterator<Item = (ObjectID, Arc<ReserveRegistry>)> {
+        self.0
+            .iter()
+            .map(|entry| (*entry.key(), entry.value().clone()))
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+}
+
+/// Reserve configs of a lending market keyed by `reserve_array_index`
+#[derive(Debug, Clone, Default)]
+pub struct ReserveRegistry(HashMap<u64, ReserveInfo>);
+
+impl ReserveRegistry {
+    #[cfg(test)]
+    pub fn from_reserves(reserves: impl IntoIterator<Item = ReserveInfo>) -> Self {
+        Self(
+            reserves
+                .into_iter()
+                .map(|reserve| (reserve.array_index, reserve))
+                .collect(),
+        )
+    }
+
+    pub async fn load(rpc: &RpcPool, lending_market_id: ObjectID) -> Result<Self, Errors> {
+        let market = rpc
+            .call(|client| async move {
+                Ok(client
+                    .read_api()
+                    .get_object_with_options(
+                        lending_market_id,
+                        SuiObjectDataOptions {
+                            show_content: true,
+                            show_type: true,
+                            show_owner: false,
+                            show_previous_transaction: false,
+                            show_display: false,
+                            show_bcs: false,
+                            show_storage_rebate: false,
+                        },
+                    )
+                    .await?)
+            })
+            .await?
+            .data
+            .ok_or(Errors::ObjectNotFound(lending_market_id))?;
+        let layout = |reason: String| Errors::UnexpectedMoveLayout {
+            object_id: lending_market_id,
+            type_name: "LendingMarket",
+            reason,
+        };
+        let market = market
+            .content
+            .and_then(|content| content.try_into_move())
+            .ok_or_else(|| layout("not a Move object".to_string()))?;
+
+        let market: LendingMarket = serde_json::from_value(market.fields.to_json_value())
+            .map_err(|e| layout(e.to_string()))?;
+
+        // A reserve that doesn't parse only costs the obligations using it, not the market
+        let mut registry = ReserveRegistry::default();
+        for reserve in market.reserves {
+            let array_index = reserve.array_index.clone();
+            match ReserveInfo::try_from(reserve) {
+                Ok(info) => {
+                    registry.0.insert(info.array_index, info);
+                }
+                Err(e) => warn!(
+                    "Skipping reserve {} of market {}: {}",
+                    array_index,
+                    lending_market_id,
+                    layout(e.to_string())
+                ),
+            }
+        }
+
+        for reserve in registry.iter() {
+            coins::set_decimals(&reserve.coin_type, reserve.mint_decimals);
+        }
+        let reward_coins = registry
+            .iter()
+            .flat_map(|reserve| {
+                reserve
+                    .deposit_rewards
+                    .iter()
+                    .chain(reserve.borrow_rewards.iter())
+            })
+            .map(|reward| reward.coin_type.as_str())
+            .collect::<BTreeSet<_>>();
+        for coin_type in reward_coins {
+            if let Err(e) = coins::load_decimals(rpc, coin_type).await {
+                warn!("Cannot count {} rewards: {}", coin_type, e);
+            }
+        }
+        Ok(registry)
+    }
+
+    pub fn get(&self, reserve_array_index: u64) -> Option<&ReserveInfo> {
+        self.0.get(&reserve_array_index)
+    }
+
+    pub fn by_index(&self, reserve_array_index: &str) -> Result<&ReserveInfo, Errors> {
+        let index = reserve_array_index
+            .parse::<u64>()
+            .map_err(|_| Errors::MoveObjectParsingError("reserve_array_index"))?;
+        self.get(index).ok_or(Errors::ReserveNotFound(index))
+    }
+
+    pub fn by_coin(&self, coin_type: &str) -> Result<&ReserveInfo, Errors> {
+        let coin_type = coins::normalize(coin_type);
+        self.iter()
+            .find(|reserve| coins::normalize(&reserve.coin_type) == coin_type)
+            .ok_or_else(|| Errors::NoReserveForCoin(coin_type.to_string()))
+    }
+
+    /// The Pyth feed of any coin, e.g. a reward coin, through its reserve or the fallback table
+    pub fn feed_for(&self, coin_type: &str) -> Option<FeedId> {
+        self.by_coin(coin_type)
+            .and_then(|reserve| reserve.feed())
+            .ok()
+            .or_else(|| coins::fallback(coin_type))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &ReserveInfo> {
+        self.0.values()
+    }
+}
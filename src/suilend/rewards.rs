@@ -0,0 +1,464 @@
+use super::{
+    coins,
+    decimal::SuilendDecimal,
+    health::TrackedObligation,
+    interest::{apr_to_apy, SECONDS_PER_YEAR},
+    objects::UserRewardManager,
+    reserves::{ReserveRegistry, RewardPool},
+};
+use crate::{errors::Errors, pyth::prices::LATEST_PRICES};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt};
+use sui_sdk::types::base_types::ObjectID;
+
+const MS_PER_YEAR: f64 = SECONDS_PER_YEAR as f64 * 1000.0;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RewardsConfig {
+    /// How often unclaimed rewards and position yields are recomputed and logged
+    pub interval_secs: u64,
+    /// Alert once an obligation's unclaimed rewards are worth this many USD
+    pub alert_above_usd: Option<f64>,
+}
+
+impl Default for RewardsConfig {
+    fn default() -> Self {
+        Self {
+            interval_secs: 300,
+            alert_above_usd: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Deposit,
+    Borrow,
+}
+
+impl fmt::Display for Side {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Side::Deposit => write!(f, "Deposit"),
+            Side::Borrow => write!(f, "Borrow"),
+        }
+    }
+}
+
+/// Rewards of one coin accrued across the obligation but not yet claimed
+#[derive(Debug, Clone, Serialize)]
+pub struct Unclaimed {
+    pub coin_type: String,
+    /// Raw units, what `claim_rewards` would pay out
+    pub amount: u64,
+    /// `None` while the coin's decimals are unknown
+    pub tokens: Option<f64>,
+    pub value_usd: Option<f64>,
+}
+
+/// Yield of one deposit or borrow, as fractions per year
+#[derive(Debug, Clone, Serialize)]
+pub struct PositionYield {
+    pub side: Side,
+    pub coin: String,
+    pub value_usd: f64,
+    /// Earned on a deposit, paid on a borrow
+    pub interest_apy: f64,
+    /// Rewards whose coin has no price yet are left out
+    pub reward_apr: f64,
+    /// Rewards plus deposit interest, or minus borrow interest
+    pub net_apy: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RewardsReport {
+    pub obligation_id: ObjectID,
+    pub label: String,
+    pub unclaimed: Vec<Unclaimed>,
+    pub positions: Vec<PositionYield>,
+}
+
+/// Parses a number field of the obligation `object_id`
+fn parse(object_id: ObjectID, field: &'static str, value: &str) -> Result<u64, Errors> {
+    value
+        .parse::<u64>()
+        .map_err(|_| Errors::ObjectFieldParsingError { object_id, field })
+}
+
+fn price_usd(reserves: &ReserveRegistry, coin_type: &str) -> Option<f64> {
+    let feed = reserves.feed_for(coin_type)?;
+    LATEST_PRICES.get(&feed).map(|price| price.to_f64())
+}
+
+/// What `manager` of `obligation_id` would have earned per reward slot had
+/// `update_user_reward_manager` run at `now_ms`
+fn pending(
+    obligation_id: ObjectID,
+    pool: &RewardPool,
+    manager: &UserRewardManager,
+    now_ms: u64,
+) -> Result<Vec<(String, SuilendDecimal)>, Errors> {
+    let share = SuilendDecimal::from_integer(parse(obligation_id, "share", &manager.share)?);
+    let last_update_ms = parse(
+        obligation_id,
+        "last_update_time_ms",
+        &manager.last_update_time_ms,
+    )?;
+
+    let mut pending = Vec::new();
+    for (slot, reward) in pool.rewards.iter().enumerate() {
+        let Some(reward) = reward else {
+            continue;
+        };
+        let cumulative = pool.cumulative_at(reward, now_ms)?;
+        let earned = match manager.rewards.get(slot).and_then(Option::as_ref) {
+            Some(user) if user.pool_reward_id == reward.id => user.earned_rewards.checked_add(
+                cumulative
+                    .saturating_sub(user.cumulative_rewards_per_share)
+                    .checked_mul(share)?,
+            )?,
+            // Joined before the program started, so everything it paid so far is ours
+            None if last_update_ms <= reward.start_time_ms => cumulative.checked_mul(share)?,
+            _ => continue,
+        };
+        if !earned.is_zero() {
+            pending.push((reward.coin_type.clone(), earned));
+        }
+    }
+    Ok(pending)
+}
+
+/// Yearly USD paid to `manager`'s share of the active programs, over the position's value
+fn reward_apr(
+    obligation_id: ObjectID,
+    pool: &RewardPool,
+    manager: &UserRewardManager,
+    reserves: &ReserveRegistry,
+    value_usd: f64,
+    now_ms: u64,
+) -> Result<f64, Errors> {
+    if pool.total_shares == 0 || value_usd <= 0.0 {
+        return Ok(0.0);
+    }
+    let share = parse(obligation_id, "share", &manager.share)? as f64 / pool.total_shares as f64;
+
+    let mut yearly_usd = 0.0;
+    for reward in pool.iter().filter(|reward| reward.is_active(now_ms)) {
+        let (Some(decimals), Some(price)) = (
+            coins::decimals(&reward.coin_type),
+            price_usd(reserves, &reward.coin_type),
+        ) else {
+            continue;
+        };
+        let duration_ms = (reward.end_time_ms - reward.start_time_ms) as f64;
+        let tokens = reward.total_rewards as f64 / 10f64.powi(decimals as i32);
+        yearly_usd += tokens * MS_PER_YEAR / duration_ms * price * share;
+    }
+    Ok(yearly_usd / value_usd)
+}
+
+impl RewardsReport {
+    pub fn new(
+        tracked: &TrackedObligation,
+        reserves: &ReserveRegistry,
+        now_ms: u64,
+    ) -> Result<Self, Errors> {
+        let obligation = &tracked.obligation;
+        let deposits = obligation.deposits.iter().map(|d| {
+            (
+                Side::Deposit,
+                &d.reserve_array_index,
+                &d.user_reward_manager_index,
+            )
+        });
+        let borrows = obligation.borrows.iter().map(|b| {
+            (
+                Side::Borrow,
+                &b.reserve_array_index,
+                &b.user_reward_manager_index,
+            )
+        });
+
+        let mut earned = BTreeMap::<String, SuilendDecimal>::new();
+        let mut positions = Vec::new();
+        for (side, reserve_index, manager_index) in deposits.chain(borrows) {
+            let reserve = reserves.by_index(reserve_index)?;
+            let leg = match side {
+                Side::Deposit => tracked.deposit_leg(reserve.array_index),
+                Side::Borrow => tracked.borrow_leg(reserve.array_index),
+            }
+            .ok_or(Errors::ReserveNotFound(reserve.array_index))?;
+            let (pool, interest_apr) = match side {
+                Side::Deposit => (&reserve.deposit_rewards, reserve.supply_apr()?),
+                Side::Borrow => (&reserve.borrow_rewards, reserve.current_apr()?),
+            };
+            let value_usd = leg.current_value()?.to_f64();
+            let manager = parse(tracked.id, "user_reward_manager_index", manager_index)
+                .ok()
+                .and_then(|index| obligation.user_reward_managers.get(index as usize))
+                .filter(|manager| {
+                    pool.id
+                        .is_none_or(|id| id == manager.pool_reward_manager_id)
+                });
+
+            let mut rewards_apr = 0.0;
+            if let Some(manager) = manager {
+                for (coin_type, amount) in pending(tracked.id, pool, manager, now_ms)? {
+                    let total = earned.entry(coin_type).or_default();
+                    *total = total.checked_add(amount)?;
+                }
+                rewards_apr = reward_apr(tracked.id, pool, manager, reserves, value_usd, now_ms)?;
+            }
+
+            let interest_apy = apr_to_apy(interest_apr.to_f64());
+            positions.push(PositionYield {
+                side,
+                coin: coins::symbol(&reserve.coin_type).to_string(),
+                value_usd,
+                interest_apy,
+                reward_apr: rewards_apr,
+                net_apy: match side {
+                    Side::Deposit => rewards_apr + interest_apy,
+                    Side::Borrow => rewards_apr - interest_apy,
+                },
+            });
+        }
+
+        let unclaimed = earned
+            .into_iter()
+            .map(|(coin_type, amount)| {
+                // Claims pay out whole raw units
+                let amount = u64::try_from(amount.floor()).unwrap_or(u64::MAX);
+                let tokens = coins::decimals(&coin_type)
+                    .map(|decimals| amount as f64 / 10f64.powi(decimals as i32));
+                let value_usd = tokens
+                    .zip(price_usd(reserves, &coin_type))
+                    .map(|(tokens, price)| tokens * price);
+                Unclaimed {
+                    coin_type,
+                    amount,
+                    tokens,
+                    value_usd,
+                }
+            })
+            .filter(|unclaimed| unclaimed.amount > 0)
+            .collect();
+
+        Ok(Self {
+            obligation_id: tracked.id,
+            label: tracked.label.clone(),
+            unclaimed,
+            positions,
+        })
+    }
+
+    /// USD value of the rewards that can be priced
+    pub fn unclaimed_usd(&self) -> f64 {
+        self.unclaimed
+            .iter()
+            .filter_map(|unclaimed| unclaimed.value_usd)
+            .sum()
+    }
+}
+
+impl fmt::Display for RewardsReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "[{}] {} unclaimed rewards:",
+            self.label, self.obligation_id
+        )?;
+        if self.unclaimed.is_empty() {
+            write!(f, " none")?;
+        }
+        for unclaimed in self.unclaimed.iter() {
+            let symbol = coins::symbol(&unclaimed.coin_type);
+            match unclaimed.tokens {
+                Some(tokens) => write!(f, "\n  {:.6} {}", tokens, symbol)?,
+                None => write!(f, "\n  {} raw {}", unclaimed.amount, symbol)?,
+            }
+            if let Some(value) = unclaimed.value_usd {
+                write!(f, " (${:.2})", value)?;
+            }
+        }
+        for position in self.positions.iter() {
+            write!(
+                f,
+                "\n  {} {} (${:.2}): interest {:.2}%, rewards {:.2}%, net {:.2}%",
+                position.side,
+                position.coin,
+                position.value_usd,
+                position.interest_apy * 100.0,
+                position.reward_apr * 100.0,
+                position.net_apy * 100.0
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        pyth::{prices::PythPrice, types::FeedId},
+        suilend::{
+            objects::UserReward,
+            reserves::RewardInfo,
+            testing::{deposit, obligation, reserve, OBLIGATION, SUI, USDC},
+        },
+    };
+
+    const YEAR_MS: u64 = SECONDS_PER_YEAR * 1000;
+    const POOL: ObjectID = ObjectID::new([5; 32]);
+    const REWARD: ObjectID = ObjectID::new([6; 32]);
+
+    /// 1000 SUI paid over a year to 100 shares, none of it distributed yet
+    fn pool() -> RewardPool {
+        RewardPool {
+            id: Some(POOL),
+            total_shares: 100,
+            last_update_time_ms: 0,
+            rewards: vec![
+                None,
+                Some(RewardInfo {
+                    id: REWARD,
+                    coin_type: SUI.to_string(),
+                    start_time_ms: 0,
+                    end_time_ms: YEAR_MS,
+                    total_rewards: 1_000_000_000_000,
+                    cumulative_rewards_per_share: SuilendDecimal::zero(),
+                }),
+            ],
+        }
+    }
+
+    /// 10 of the pool's shares, last updated at `last_update_ms`
+    fn manager(last_update_ms: u64, rewards: Vec<Option<UserReward>>) -> UserRewardManager {
+        UserRewardManager {
+            pool_reward_manager_id: POOL,
+            share: "10".to_string(),
+            rewards,
+            last_update_time_ms: last_update_ms.to_string(),
+        }
+    }
+
+    fn user_reward(id: ObjectID, earned: u64, cumulative: u64) -> Option<UserReward> {
+        Some(UserReward {
+            pool_reward_id: id,
+            earned_rewards: SuilendDecimal::from_integer(earned),
+            cumulative_rewards_per_share: SuilendDecimal::from_integer(cumulative),
+        })
+    }
+
+    fn raw(amount: u64) -> Vec<(String, SuilendDecimal)> {
+        vec![(SUI.to_string(), SuilendDecimal::from_integer(amount))]
+    }
+
+    #[test]
+    fn accrues_from_the_user_snapshot() {
+        // Half the program unlocked: 5e9 per share since the user's snapshot of zero
+        let manager = manager(0, vec![None, user_reward(REWARD, 7, 0)]);
+        assert_eq!(
+            pending(OBLIGATION, &pool(), &manager, YEAR_MS / 2).unwrap(),
+            raw(50_000_000_007)
+        );
+
+        // A user reward created once 2e9 per share had been paid only earns the rest
+        let manager = self::manager(0, vec![None, user_reward(REWARD, 0, 2_000_000_000)]);
+        assert_eq!(
+            pending(OBLIGATION, &pool(), &manager, YEAR_MS / 2).unwrap(),
+            raw(30_000_000_000)
+        );
+    }
+
+    #[test]
+    fn credits_a_missing_user_reward_only_from_before_the_start() {
+        let mut pool = pool();
+        if let Some(reward) = pool.rewards[1].as_mut() {
+            reward.start_time_ms = 1_000;
+        }
+        // Not updated since before the program began, so it earned from the start
+        let early = manager(1_000, vec![]);
+        assert_eq!(
+            pending(OBLIGATION, &pool, &early, 1_000 + (YEAR_MS - 1_000) / 2).unwrap(),
+            raw(50_000_000_000)
+        );
+        // Joined after it began: `update_user_reward_manager` would snapshot, not pay
+        let late = manager(2_000, vec![]);
+        assert_eq!(
+            pending(OBLIGATION, &pool, &late, YEAR_MS / 2).unwrap(),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn ignores_slots_of_other_programs() {
+        let manager = manager(0, vec![None, user_reward(ObjectID::new([9; 32]), 7, 0)]);
+        assert_eq!(
+            pending(OBLIGATION, &pool(), &manager, YEAR_MS / 2).unwrap(),
+            vec![]
+        );
+        assert!(pending(OBLIGATION, &pool(), &self::manager(0, vec![]), 0)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn reports_rewards_against_the_leg_of_their_reserve() {
+        let (sui, usdc) = (
+            FeedId::from_bytes([0x60; 32]),
+            FeedId::from_bytes([0x61; 32]),
+        );
+        LATEST_PRICES.insert(sui, PythPrice::new(200, 0, -2, 0));
+        LATEST_PRICES.insert(usdc, PythPrice::new(100, 0, -2, 0));
+        coins::set_decimals(SUI, 9);
+
+        let mut sui_reserve = reserve(0, SUI, 9, sui);
+        sui_reserve.deposit_rewards = pool();
+        let reserves = ReserveRegistry::from_reserves([sui_reserve, reserve(7, USDC, 6, usdc)]);
+
+        // 500 USDC listed ahead of 1000 SUI, whose deposit holds the rewards
+        let mut usdc_deposit = deposit(7, USDC, 500_000_000);
+        usdc_deposit.user_reward_manager_index = "1".to_string();
+        let mut obligation = obligation(
+            vec![usdc_deposit, deposit(0, SUI, 1_000_000_000_000)],
+            vec![],
+        );
+        obligation.user_reward_managers = vec![
+            manager(0, vec![None, user_reward(REWARD, 0, 0)]),
+            UserRewardManager {
+                pool_reward_manager_id: ObjectID::new([8; 32]),
+                ..manager(0, vec![])
+            },
+        ];
+        let mut tracked =
+            TrackedObligation::new("test".to_string(), &obligation, &reserves).unwrap();
+        // Leg order must not matter
+        tracked.deposits.reverse();
+
+        let report = RewardsReport::new(&tracked, &reserves, YEAR_MS / 2).unwrap();
+        assert_eq!(report.unclaimed.len(), 1);
+        assert_eq!(report.unclaimed[0].amount, 50_000_000_000);
+        assert_eq!(report.unclaimed[0].tokens, Some(50.0));
+        assert_eq!(report.unclaimed[0].value_usd, Some(100.0));
+
+        let [usdc_yield, sui_yield] = report.positions.as_slice() else {
+            panic!("expected two positions, got {:?}", report.positions);
+        };
+        assert_eq!(
+            (usdc_yield.coin.as_str(), usdc_yield.value_usd),
+            ("USDC", 500.0)
+        );
+        assert_eq!(usdc_yield.reward_apr, 0.0);
+        // 10% of 1000 SUI a year at $2 over a $2000 deposit
+        assert_eq!(
+            (sui_yield.coin.as_str(), sui_yield.value_usd),
+            ("SUI", 2000.0)
+        );
+        assert!((sui_yield.reward_apr - 0.1).abs() < 1e-9);
+        assert_eq!(sui_yield.net_apy, sui_yield.reward_apr);
+    }
+}
@@ -0,0 +1,548 @@
+use super::{
+    decimal::SuilendDecimal,
+    health::{TrackedObligation, TRACKED_OBLIGATIONS},
+    objects::{Borrow, Obligation, SuilendAccount},
+    reserves::{MarketConfig, Markets, ReserveRegistry},
+};
+use crate::{errors::Errors, rpc::RpcPool};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt,
+    sync::Arc,
+    time::Duration,
+};
+use sui_sdk::types::base_types::ObjectID;
+use tokio::{
+    sync::{broadcast, mpsc},
+    task::AbortHandle,
+    time::Instant,
+};
+use tracing::{info, warn};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SyncConfig {
+    /// How often every tracked obligation is re-read from chain
+    pub interval_secs: u64,
+}
+
+impl Default for SyncConfig {
+    fn default() -> Self {
+        Self { interval_secs: 60 }
+    }
+}
+
+/// What changed in an obligation between two reads, amounts in whole tokens
+#[derive(Debug, Clone, PartialEq)]
+pub enum PositionChange {
+    DepositAdded {
+        coin: String,
+        from: SuilendDecimal,
+        to: SuilendDecimal,
+    },
+    DepositWithdrawn {
+        coin: String,
+        from: SuilendDecimal,
+        to: SuilendDecimal,
+    },
+    NewBorrow {
+        coin: String,
+        amount: SuilendDecimal,
+    },
+    BorrowIncreased {
+        coin: String,
+        from: SuilendDecimal,
+        to: SuilendDecimal,
+    },
+    /// Partially or, when `to` is zero, fully repaid
+    Repaid {
+        coin: String,
+        from: SuilendDecimal,
+        to: SuilendDecimal,
+    },
+    /// Every deposit and borrow is gone, or the object no longer exists
+    Closed,
+}
+
+impl fmt::Display for PositionChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PositionChange::DepositAdded { coin, from, to } => {
+                write!(f, "{} deposit added: {:.6} -> {:.6}", coin, from, to)
+            }
+            PositionChange::DepositWithdrawn { coin, from, to } => {
+                write!(f, "{} deposit withdrawn: {:.6} -> {:.6}", coin, from, to)
+            }
+            PositionChange::NewBorrow { coin, amount } => {
+                write!(f, "New {} borrow: {:.6}", coin, amount)
+            }
+            PositionChange::BorrowIncreased { coin, from, to } => {
+                write!(f, "{} borrow increased: {:.6} -> {:.6}", coin, from, to)
+            }
+            PositionChange::Repaid { coin, to, .. } if to.is_zero() => {
+                write!(f, "{} borrow fully repaid", coin)
+            }
+            PositionChange::Repaid { coin, from, to } => {
+                write!(f, "{} borrow repaid: {:.6} -> {:.6}", coin, from, to)
+            }
+            PositionChange::Closed => write!(f, "Obligation closed"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PositionEvent {
+    pub obligation_id: ObjectID,
+    pub label: String,
+    pub change: PositionChange,
+}
+
+impl fmt::Display for PositionEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "[{}] {}: {}",
+            self.label, self.obligation_id, self.change
+        )
+    }
+}
+
+/// Deposits keyed by reserve index, as (coin, whole tokens)
+fn deposits(
+    obligation: &Obligation,
+    reserves: &ReserveRegistry,
+) -> Result<BTreeMap<String, (String, SuilendDecimal)>, Errors> {
+    obligation
+        .deposits
+        .iter()
+        .map(|d| {
+            let reserve = reserves.by_index(&d.reserve_array_index)?;
+            let amount =
+                reserve.to_tokens(reserve.ctokens_to_underlying(d.ctokens(obligation.id.id)?)?)?;
+            Ok((
+                d.reserve_array_index.clone(),
+                (d.coin_type.symbol().to_string(), amount),
+            ))
+        })
+        .collect()
+}
+
+/// `previous`'s debt grown to `current`'s cumulative borrow rate, i.e. with interest only
+fn accrued(previous: &Borrow, current: &Borrow) -> Result<SuilendDecimal, Errors> {
+    previous
+        .borrowed_amount
+        .checked_mul(current.cumulative_borrow_rate)?
+        .checked_div(previous.cumulative_borrow_rate)
+}
+
+/// Compares two reads of the same obligation.
+///
+/// Borrows are compared after accruing interest on the earlier one, so only
+/// actual borrows and repayments count as changes.
+pub fn diff(
+    previous: &Obligation,
+    current: &Obligation,
+    reserves: &ReserveRegistry,
+) -> Result<Vec<PositionChange>, Errors> {
+    let mut changes = Vec::new();
+
+    let before = deposits(previous, reserves)?;
+    let after = deposits(current, reserves)?;
+    for (index, (coin, to)) in after.iter() {
+        let from = before
+            .get(index)
+            .map(|(_, amount)| *amount)
+            .unwrap_or_default();
+        if *to > from {
+            changes.push(PositionChange::DepositAdded {
+                coin: coin.clone(),
+                from,
+                to: *to,
+            });
+        } else if *to < from {
+            changes.push(PositionChange::DepositWithdrawn {
+                coin: coin.clone(),
+                from,
+                to: *to,
+            });
+        }
+    }
+    for (index, (coin, from)) in before.iter() {
+        if !after.contains_key(index) {
+            changes.push(PositionChange::DepositWithdrawn {
+                coin: coin.clone(),
+                from: *from,
+                to: SuilendDecimal::zero(),
+            });
+        }
+    }
+
+    for borrow in current.borrows.iter() {
+        let reserve = reserves.by_index(&borrow.reserve_array_index)?;
+        let coin = borrow.coin_type.symbol().to_string();
+        let to = reserve.to_tokens(borrow.borrowed_amount)?;
+        let Some(earlier) = previous
+            .borrows
+            .iter()
+            .find(|b| b.reserve_array_index == borrow.reserve_array_index)
+        else {
+            changes.push(PositionChange::NewBorrow { coin, amount: to });
+            continue;
+        };
+        let expected = accrued(earlier, borrow)?;
+        // Less than one raw unit apart is rounding in the interest index
+        if borrow
+            .borrowed_amount
+            .max(expected)
+            .saturating_sub(borrow.borrowed_amount.min(expected))
+            < SuilendDecimal::one()
+        {
+            continue;
+        }
+        let from = reserve.to_tokens(earlier.borrowed_amount)?;
+        if borrow.borrowed_amount > expected {
+            changes.push(PositionChange::BorrowIncreased { coin, from, to });
+        } else {
+            changes.push(PositionChange::Repaid { coin, from, to });
+        }
+    }
+    for earlier in previous.borrows.iter() {
+        if !current
+            .borrows
+            .iter()
+            .any(|b| b.reserve_array_index == earlier.reserve_array_index)
+        {
+            let reserve = reserves.by_index(&earlier.reserve_array_index)?;
+            changes.push(PositionChange::Repaid {
+                coin: earlier.coin_type.symbol().to_string(),
+                from: reserve.to_tokens(earlier.borrowed_amount)?,
+                to: SuilendDecimal::zero(),
+            });
+        }
+    }
+
+    let was_open = !previous.deposits.is_empty() || !previous.borrows.is_empty();
+    if was_open && current.deposits.is_empty() && current.borrows.is_empty() {
+        changes.push(PositionChange::Closed);
+    }
+    Ok(changes)
+}
+
+/// Re-reads tracked obligations on an interval, or right away on request,
+/// and broadcasts every position change it sees
+pub struct ObligationSync {
+    requests: mpsc::UnboundedSender<ObjectID>,
+    changes: broadcast::Sender<PositionEvent>,
+    task: AbortHandle,
+}
+
+impl ObligationSync {
+    pub fn spawn(
+        rpc: Arc<RpcPool>,
+        markets: Markets,
+        config: &SyncConfig,
+        market: &MarketConfig,
+    ) -> Self {
+        let (requests, receiver) = mpsc::unbounded_channel();
+        let (changes, _) = broadcast::channel(256);
+        let refresher = Refresher {
+            rpc,
+            markets,
+            market: market.clone(),
+            changes: changes.clone(),
+        };
+        let task = tokio::spawn(refresher.run(receiver, Duration::from_secs(config.interval_secs)))
+            .abort_handle();
+        Self {
+            requests,
+            changes,
+            task,
+        }
+    }
+
+    /// Re-reads `obligation_id` now instead of at the next interval
+    pub fn refresh(&self, obligation_id: ObjectID) {
+        let _ = self.requests.send(obligation_id);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<PositionEvent> {
+        self.changes.subscribe()
+    }
+}
+
+impl Drop for ObligationSync {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+struct Refresher {
+    rpc: Arc<RpcPool>,
+    markets: Markets,
+    market: MarketConfig,
+    changes: broadcast::Sender<PositionEvent>,
+}
+
+impl Refresher {
+    async fn run(self, mut requests: mpsc::UnboundedReceiver<ObjectID>, interval: Duration) {
+        // Obligations were just loaded, so the first full pass waits one interval
+        let mut ticker = tokio::time::interval_at(Instant::now() + interval, interval);
+        loop {
+            let ids = tokio::select! {
+                _ = ticker.tick() => TRACKED_OBLIGATIONS.iter().map(|e| *e.key()).collect(),
+                request = requests.recv() => match request {
+                    Some(id) => {
+                        let mut ids = vec![id];
+                        while let Ok(id) = requests.try_recv() {
+                            if !ids.contains(&id) {
+                                ids.push(id);
+                            }
+                        }
+                        ids
+                    }
+                    None => return,
+                },
+            };
+            self.refresh(ids).await;
+        }
+    }
+
+    async fn refresh(&self, ids: Vec<ObjectID>) {
+        if ids.is_empty() {
+            return;
+        }
+        // Fresh reserves so interest indices and ctoken ratios match the new reads, once per
+        // market and pass
+        let mut reloaded = HashSet::new();
+        for id in ids {
+            let Some(lending_market_id) = TRACKED_OBLIGATIONS
+                .get(&id)
+                .map(|t| t.obligation.lending_market_id)
+            else {
+                continue;
+            };
+            let reserves = if reloaded.insert(lending_market_id) {
+                self.reload(lending_market_id).await
+            } else {
+                self.markets.get(&lending_market_id)
+            };
+            let reserves = match reserves {
+                Ok(reserves) => reserves,
+                Err(e) => {
+                    warn!(
+                        "Skipping re-sync of obligation {}, cannot load reserves: {}",
+                        id, e
+                    );
+                    continue;
+                }
+            };
+            for event in self.refresh_obligation(id, &reserves).await {
+                info!("{}", event);
+                let _ = self.changes.send(event);
+            }
+        }
+    }
+
+    /// Re-reads a market's reserves, falling back to the cached ones if that fails
+    async fn reload(&self, lending_market_id: ObjectID) -> Result<Arc<ReserveRegistry>, Errors> {
+        match self.markets.reload(&self.rpc, lending_market_id).await {
+            Ok(reserves) => Ok(reserves),
+            Err(e) => {
+                warn!(
+                    "Keeping cached reserves of market {}: {}",
+                    lending_market_id, e
+                );
+                self.markets.get(&lending_market_id)
+            }
+        }
+    }
+
+    async fn refresh_obligation(
+        &self,
+        id: ObjectID,
+        reserves: &ReserveRegistry,
+    ) -> Vec<PositionEvent> {
+        let Some(label) = TRACKED_OBLIGATIONS.get(&id).map(|t| t.label.clone()) else {
+            return Vec::new();
+        };
+        let event = |change| PositionEvent {
+            obligation_id: id,
+            label: label.clone(),
+            change,
+        };
+
+        let current = match SuilendAccount::get_obligation(&self.rpc, &self.market, id).await {
+            Ok(current) => current,
+            Err(Errors::ObjectNotFound(_)) => {
+                TRACKED_OBLIGATIONS.remove(&id);
+                return vec![event(PositionChange::Closed)];
+            }
+            Err(e) => {
+                warn!("Failed to re-sync obligation {}: {}", id, e);
+                return Vec::new();
+            }
+        };
+        let tracked = match TrackedObligation::new(label.clone(), &current, reserves) {
+            Ok(tracked) => tracked,
+            Err(e) => {
+                warn!("Failed to re-sync obligation {}: {}", id, e);
+                return Vec::new();
+            }
+        };
+
+        // Dropped from the watch list while the read was in flight
+        let Some(mut entry) = TRACKED_OBLIGATIONS.get_mut(&id) else {
+            return Vec::new();
+        };
+        let changes = diff(&entry.obligation, &current, reserves).unwrap_or_else(|e| {
+            warn!("Cannot compare obligation {} with its last read: {}", id, e);
+            Vec::new()
+        });
+        *entry = tracked;
+        changes.into_iter().map(event).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        pyth::types::FeedId,
+        suilend::testing::{borrow, deposit, obligation, registry, SUI, USDC},
+    };
+
+    fn reserves() -> ReserveRegistry {
+        registry(
+            FeedId::from_bytes([0x51; 32]),
+            FeedId::from_bytes([0x52; 32]),
+        )
+    }
+
+    fn tokens(amount: u64) -> SuilendDecimal {
+        SuilendDecimal::from_integer(amount)
+    }
+
+    fn rate(milli: u64) -> SuilendDecimal {
+        SuilendDecimal::from_integer(milli)
+            .checked_div(SuilendDecimal::from_integer(1000))
+            .unwrap()
+    }
+
+    #[test]
+    fn reports_nothing_when_only_interest_accrued() {
+        let previous = obligation(
+            vec![deposit(0, SUI, 2_000_000_000)],
+            vec![borrow(7, USDC, 100_000_000, rate(1000))],
+        );
+        // 10% interest on both the reserve index and the debt
+        let current = obligation(
+            vec![deposit(0, SUI, 2_000_000_000)],
+            vec![borrow(7, USDC, 110_000_000, rate(1100))],
+        );
+        assert_eq!(diff(&previous, &current, &reserves()).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn reports_opened_deposits_and_borrows() {
+        let previous = obligation(vec![], vec![]);
+        let current = obligation(
+            vec![deposit(0, SUI, 2_000_000_000)],
+            vec![borrow(7, USDC, 100_000_000, rate(1000))],
+        );
+        assert_eq!(
+            diff(&previous, &current, &reserves()).unwrap(),
+            vec![
+                PositionChange::DepositAdded {
+                    coin: "SUI".to_string(),
+                    from: tokens(0),
+                    to: tokens(2),
+                },
+                PositionChange::NewBorrow {
+                    coin: "USDC".to_string(),
+                    amount: tokens(100),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_increases() {
+        let previous = obligation(
+            vec![deposit(0, SUI, 2_000_000_000)],
+            vec![borrow(7, USDC, 100_000_000, rate(1000))],
+        );
+        let current = obligation(
+            vec![deposit(0, SUI, 5_000_000_000)],
+            vec![borrow(7, USDC, 150_000_000, rate(1000))],
+        );
+        assert_eq!(
+            diff(&previous, &current, &reserves()).unwrap(),
+            vec![
+                PositionChange::DepositAdded {
+                    coin: "SUI".to_string(),
+                    from: tokens(2),
+                    to: tokens(5),
+                },
+                PositionChange::BorrowIncreased {
+                    coin: "USDC".to_string(),
+                    from: tokens(100),
+                    to: tokens(150),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_decreases_against_accrued_debt() {
+        let previous = obligation(
+            vec![deposit(0, SUI, 5_000_000_000)],
+            vec![borrow(7, USDC, 100_000_000, rate(1000))],
+        );
+        // 105 USDC owed after interest, 104 of it left: a repayment of one
+        let current = obligation(
+            vec![deposit(0, SUI, 3_000_000_000)],
+            vec![borrow(7, USDC, 104_000_000, rate(1050))],
+        );
+        assert_eq!(
+            diff(&previous, &current, &reserves()).unwrap(),
+            vec![
+                PositionChange::DepositWithdrawn {
+                    coin: "SUI".to_string(),
+                    from: tokens(5),
+                    to: tokens(3),
+                },
+                PositionChange::Repaid {
+                    coin: "USDC".to_string(),
+                    from: tokens(100),
+                    to: tokens(104),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_closed_positions() {
+        let previous = obligation(
+            vec![deposit(0, SUI, 2_000_000_000)],
+            vec![borrow(7, USDC, 100_000_000, rate(1000))],
+        );
+        let current = obligation(vec![], vec![]);
+        assert_eq!(
+            diff(&previous, &current, &reserves()).unwrap(),
+            vec![
+                PositionChange::DepositWithdrawn {
+                    coin: "SUI".to_string(),
+                    from: tokens(2),
+                    to: tokens(0),
+                },
+                PositionChange::Repaid {
+                    coin: "USDC".to_string(),
+                    from: tokens(100),
+                    to: tokens(0),
+                },
+                PositionChange::Closed,
+            ]
+        );
+    }
+}
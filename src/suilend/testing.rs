@@ -0,0 +1,89 @@
+//! Reserves and obligations for unit tests, built by hand rather than read from chain
+
+use super::{
+    decimal::SuilendDecimal,
+    interest::InterestRateCurve,
+    objects::{Borrow, CoinType, Deposit, ObjectUid, Obligation},
+    reserves::{ReserveInfo, ReserveRegistry, RewardPool},
+    types::{BasisPoints, Bw, CloseLtv, OpenLtv},
+};
+use crate::pyth::types::FeedId;
+use sui_sdk::types::base_types::ObjectID;
+
+pub const SUI: &str = "0x2::sui::SUI";
+pub const USDC: &str =
+    "0xdba34672e30cb065b1f93e3ab55318768fd6fef66c15942c9f7cb846e2f900e7::usdc::USDC";
+
+pub const OBLIGATION: ObjectID = ObjectID::new([1; 32]);
+pub const MARKET: ObjectID = ObjectID::new([2; 32]);
+
+/// A reserve at 1 cToken per token and a borrow index of 1, with 70% open and 80% close LTV
+pub fn reserve(array_index: u64, coin_type: &str, mint_decimals: u8, feed: FeedId) -> ReserveInfo {
+    ReserveInfo {
+        array_index,
+        coin_type: coin_type.to_string(),
+        open_ltv: OpenLtv(BasisPoints::from_pct(70)),
+        close_ltv: CloseLtv(BasisPoints::from_pct(80)),
+        borrow_weight: Bw(BasisPoints(10_000)),
+        price_identifier: Some(feed),
+        mint_decimals,
+        ctoken_ratio: SuilendDecimal::one(),
+        cumulative_borrow_rate: SuilendDecimal::one(),
+        interest_last_update_timestamp_s: 0,
+        interest_rate: InterestRateCurve::default(),
+        utilization: SuilendDecimal::zero(),
+        spread_fee: SuilendDecimal::zero(),
+        deposit_rewards: RewardPool::default(),
+        borrow_rewards: RewardPool::default(),
+    }
+}
+
+/// SUI (9 decimals) at index 0 and USDC (6 decimals) at index 7, priced by `sui` and `usdc`
+pub fn registry(sui: FeedId, usdc: FeedId) -> ReserveRegistry {
+    ReserveRegistry::from_reserves([reserve(0, SUI, 9, sui), reserve(7, USDC, 6, usdc)])
+}
+
+pub fn deposit(reserve_array_index: u64, coin_type: &str, ctokens: u64) -> Deposit {
+    Deposit {
+        attributed_borrow_value: SuilendDecimal::zero(),
+        coin_type: CoinType {
+            name: coin_type.to_string(),
+        },
+        deposited_ctoken_amount: ctokens.to_string(),
+        market_value: SuilendDecimal::zero(),
+        reserve_array_index: reserve_array_index.to_string(),
+        user_reward_manager_index: "0".to_string(),
+    }
+}
+
+/// `raw` units owed as of the `cumulative_borrow_rate` `rate`
+pub fn borrow(reserve_array_index: u64, coin_type: &str, raw: u64, rate: SuilendDecimal) -> Borrow {
+    Borrow {
+        borrowed_amount: SuilendDecimal::from_integer(raw),
+        coin_type: CoinType {
+            name: coin_type.to_string(),
+        },
+        cumulative_borrow_rate: rate,
+        market_value: SuilendDecimal::zero(),
+        reserve_array_index: reserve_array_index.to_string(),
+        user_reward_manager_index: "1".to_string(),
+    }
+}
+
+pub fn obligation(deposits: Vec<Deposit>, borrows: Vec<Borrow>) -> Obligation {
+    Obligation {
+        id: ObjectUid { id: OBLIGATION },
+        lending_market_id: MARKET,
+        allowed_borrow_value_usd: SuilendDecimal::zero(),
+        bad_debt_usd: SuilendDecimal::zero(),
+        borrowing_isolated_asset: false,
+        borrows,
+        closable: false,
+        deposited_value_usd: SuilendDecimal::zero(),
+        deposits,
+        unhealthy_borrow_value_usd: SuilendDecimal::zero(),
+        unweighted_borrowed_value_usd: SuilendDecimal::zero(),
+        weighted_borrowed_value_usd: SuilendDecimal::zero(),
+        user_reward_managers: Vec::new(),
+    }
+}
@@ -1,68 +1,62 @@
-pub struct Loan {
-    pub asset: Asset,
-    pub amount: u128,
-}
+use super::decimal::SuilendDecimal;
+use crate::errors::Errors;
 
-pub struct Deposit {
-    pub asset: Asset,
-    pub amount: u128,
-}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BasisPoints(pub u32);
 
-pub enum Asset {
-    Sui,
-    WUSDC,
-}
-
-pub struct Position {
-    pub loan: Loan,
-    pub deposit: Deposit,
-}
+impl BasisPoints {
+    pub const fn from_pct(pct: u8) -> Self {
+        BasisPoints(pct as u32 * 100)
+    }
 
-impl Position {
-    // pub fn weighted_borrow(&self) {
-    //     let wb = WeightedBorrow {
-    //         position: self.loan.asset,
-    //         price: self.loan.price,
-    //         borrow_weight: self.loan.asset.borrow_weight(),
-    //     };
-    // }
+    pub fn as_decimal(&self) -> SuilendDecimal {
+        SuilendDecimal::from_bps(self.0 as u64)
+    }
 }
 
-pub struct BasisPoints(u32);
-pub struct OpenLtv(BasisPoints);
-pub struct CloseLtv(BasisPoints);
-pub struct Bw(u8);
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpenLtv(pub BasisPoints);
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CloseLtv(pub BasisPoints);
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bw(pub BasisPoints);
 
-/// Position (borrowed) * Price ( Max(EMA, Latest Price) ) * Borrow Weight
+/// Position (borrowed) * Latest Price * Borrow Weight
+///
+/// Suilend only uses Max(EMA, Latest Price) to cap new borrows; liquidation compares the
+/// spot-priced value against the liquidation threshold
 pub struct WeightedBorrow {
-    asset: Asset,
-    amount: u128,
-    price: u128,
-    borrow_weight: Bw,
+    pub value_usd: SuilendDecimal,
+    pub borrow_weight: Bw,
 }
 
 impl WeightedBorrow {
-    pub fn calculate_weighted_borrow(&self) -> u128 {
-        self.amount * self.borrow_weight.0 as u128 * self.price
+    pub fn calculate_weighted_borrow(&self) -> Result<SuilendDecimal, Errors> {
+        self.value_usd
+            .checked_mul(self.borrow_weight.0.as_decimal())
     }
 }
 
-/// Position (deposited) * Price ( Max(EMA, Latest Price) ) * Open LTV = Total
+/// Position (deposited) * Price ( Min(EMA, Latest Price) ) * Open LTV = Total
 pub struct BorrowLimit {
-    position: Position,
-    price: u128,
-    open_ltv: OpenLtv,
+    pub value_usd: SuilendDecimal,
+    pub open_ltv: OpenLtv,
 }
 
 impl BorrowLimit {
-    pub fn get_limit(&self) -> u128 {
-        self.position.deposit.amount * self.price * (self.open_ltv.0 .0 as u128 / 10000)
+    pub fn get_limit(&self) -> Result<SuilendDecimal, Errors> {
+        self.value_usd.checked_mul(self.open_ltv.0.as_decimal())
     }
 }
 
 /// Position (deposited) * Price * Close LTV
 pub struct LiquidationThreshold {
-    position: Asset,
-    price: u128,
-    close_ltv: CloseLtv,
+    pub value_usd: SuilendDecimal,
+    pub close_ltv: CloseLtv,
+}
+
+impl LiquidationThreshold {
+    pub fn get_threshold(&self) -> Result<SuilendDecimal, Errors> {
+        self.value_usd.checked_mul(self.close_ltv.0.as_decimal())
+    }
 }
@@ -0,0 +1,202 @@
+use crate::{
+    errors::Errors,
+    rpc::RpcPool,
+    suilend::{
+        health::{TrackedObligation, TRACKED_OBLIGATIONS},
+        objects::SuilendAccount,
+        reserves::{MarketConfig, Markets},
+    },
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, fmt, path::Path, str::FromStr};
+use sui_sdk::types::base_types::{ObjectID, SuiAddress};
+use tracing::{info, warn};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Target {
+    /// Every obligation whose owner cap this wallet holds
+    Address(SuiAddress),
+    /// A single obligation, e.g. one whose cap sits in a multisig or kiosk
+    Obligation(ObjectID),
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Target::Address(address) => write!(f, "{}", address),
+            Target::Obligation(id) => write!(f, "{}", id),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct WatchTarget {
+    #[serde(default)]
+    pub label: Option<String>,
+    #[serde(flatten)]
+    pub target: Target,
+}
+
+impl WatchTarget {
+    /// Name obligations are grouped under, the target itself when unlabelled
+    pub fn label(&self) -> String {
+        self.label
+            .clone()
+            .unwrap_or_else(|| self.target.to_string())
+    }
+
+    /// Parses a CLI value of the form `[label=]0x...`
+    fn parse_labelled<T: FromStr>(
+        value: &str,
+        target: impl FnOnce(T) -> Target,
+    ) -> Result<Self, String> {
+        let (label, id) = match value.split_once('=') {
+            Some((label, id)) => (Some(label.trim().to_string()), id.trim()),
+            None => (None, value.trim()),
+        };
+        let id = id
+            .parse::<T>()
+            .map_err(|_| format!("`{}` is not a valid Sui id", id))?;
+        Ok(Self {
+            label,
+            target: target(id),
+        })
+    }
+
+    pub fn parse_address(value: &str) -> Result<Self, String> {
+        Self::parse_labelled(value, Target::Address)
+    }
+
+    pub fn parse_obligation(value: &str) -> Result<Self, String> {
+        Self::parse_labelled(value, Target::Obligation)
+    }
+}
+
+/// Watch list, e.g. in JSON: `{"targets": [{"label": "treasury", "address": "0x..."}, {"obligation": "0x..."}]}`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WatchList {
+    #[serde(default)]
+    pub targets: Vec<WatchTarget>,
+}
+
+impl WatchList {
+    /// Reads a TOML, YAML or JSON file, picked by extension
+    pub fn from_file(path: &Path) -> Result<Self, Errors> {
+        crate::config::load_file(path)
+    }
+}
+
+/// Targets currently being monitored and the obligations each one resolved to
+#[derive(Debug, Default)]
+pub struct ActiveWatch {
+    resolved: Vec<Resolved>,
+}
+
+#[derive(Debug)]
+struct Resolved {
+    target: WatchTarget,
+    ids: Vec<ObjectID>,
+    /// Whether every obligation of the target is tracked, else it is resolved again next sync
+    complete: bool,
+}
+
+impl ActiveWatch {
+    /// Brings `TRACKED_OBLIGATIONS` in line with `targets`, only fetching targets that are new.
+    ///
+    /// Targets that fail to load are skipped and retried on the next sync, as are single
+    /// obligations that can't be valued, e.g. for lack of a price feed. Returns the
+    /// obligations that are no longer watched.
+    pub async fn sync(
+        &mut self,
+        targets: &[WatchTarget],
+        rpc: &RpcPool,
+        market: &MarketConfig,
+        markets: &Markets,
+    ) -> Vec<ObjectID> {
+        let (mut kept, mut removed): (Vec<_>, Vec<_>) = std::mem::take(&mut self.resolved)
+            .into_iter()
+            .partition(|resolved| targets.contains(&resolved.target));
+
+        for target in targets {
+            let previous = kept.iter().position(|resolved| &resolved.target == target);
+            if previous.is_some_and(|i| kept[i].complete) {
+                continue;
+            }
+            match Self::track(target, rpc, market, markets).await {
+                Ok(resolved) => {
+                    info!(
+                        "Watching {} obligations for {}",
+                        resolved.ids.len(),
+                        target.label()
+                    );
+                    if let Some(i) = previous {
+                        removed.push(kept.swap_remove(i));
+                    }
+                    kept.push(resolved);
+                }
+                Err(e) => warn!("Failed to load watch target {}: {}", target.label(), e),
+            }
+        }
+
+        let still_watched = kept
+            .iter()
+            .flat_map(|resolved| resolved.ids.iter().copied())
+            .collect::<HashSet<_>>();
+        let dropped = removed
+            .into_iter()
+            .flat_map(|resolved| resolved.ids)
+            .filter(|id| !still_watched.contains(id))
+            .collect::<HashSet<_>>();
+        for id in dropped.iter() {
+            TRACKED_OBLIGATIONS.remove(id);
+        }
+
+        self.resolved = kept;
+        dropped.into_iter().collect()
+    }
+
+    async fn track(
+        target: &WatchTarget,
+        rpc: &RpcPool,
+        market: &MarketConfig,
+        markets: &Markets,
+    ) -> Result<Resolved, Errors> {
+        let obligations = match target.target {
+            Target::Address(address) => {
+                SuilendAccount::get_suilend_accounts(rpc, market, address).await?
+            }
+            Target::Obligation(id) => {
+                vec![SuilendAccount::get_obligation(rpc, market, id).await?]
+            }
+        };
+        let mut resolved = Resolved {
+            target: target.clone(),
+            ids: Vec::with_capacity(obligations.len()),
+            complete: true,
+        };
+        for obligation in obligations.iter() {
+            let tracked = match markets.ensure(rpc, obligation.lending_market_id).await {
+                Ok(reserves) => TrackedObligation::new(target.label(), obligation, &reserves),
+                Err(e) => Err(e),
+            };
+            match tracked {
+                Ok(tracked) => {
+                    info!("{}", tracked);
+                    resolved.ids.push(tracked.id);
+                    TRACKED_OBLIGATIONS.insert(tracked.id, tracked);
+                }
+                Err(e) => {
+                    warn!(
+                        "Skipping obligation {} of {}: {}",
+                        obligation.id.id,
+                        target.label(),
+                        e
+                    );
+                    resolved.complete = false;
+                }
+            }
+        }
+        Ok(resolved)
+    }
+}